@@ -0,0 +1,343 @@
+use std::io::{stdout, Write};
+
+use crate::enums::{ObjectType, PlayerCommand};
+use crate::keys;
+use crate::options::Options;
+use crate::renderer::Renderer;
+
+// How many robots are announced at every turn
+const NEAREST_ROBOTS: usize = 3;
+
+/*
+ * A renderer for screen readers: every turn is told in plain lines
+ * (where the nearest robots are, what is next to the player, the safe moves
+ * and what changed) and the whole board is described on request
+ */
+pub struct AccessibleBoard {
+    arena_width: usize,
+    arena_height: usize,
+
+    arena: Vec<ObjectType>,
+    safe_moves: Option<Vec<PlayerCommand>>,
+
+    level: u16,
+    score: u64,
+    safeteleports: u16,
+    // what has already been told
+    told: Option<(u16, u64, u16)>,
+
+    help_key: String,
+    describe_key: String,
+}
+
+impl AccessibleBoard {
+    pub fn new(options: &Options) -> Self {
+        let (arena_width, arena_height) = options.arena_size();
+
+        AccessibleBoard {
+            arena_width,
+            arena_height,
+
+            arena: Vec::new(),
+            safe_moves: None,
+
+            level: 0,
+            score: 0,
+            safeteleports: 0,
+            told: None,
+
+            help_key: keys::main_key(&options.keys.help),
+            describe_key: keys::main_key(&options.keys.describe),
+        }
+    }
+
+    fn print(&self, text: &str) {
+        let mut stdout = stdout();
+        for line in text.lines() {
+            writeln!(stdout, "{}", line).unwrap();
+        }
+        stdout.flush().unwrap();
+    }
+
+    // The level, score and safe teleports that changed since the last time
+    fn status_changes(&mut self) -> Option<String> {
+        let status = (self.level, self.score, self.safeteleports);
+
+        let text = match self.told {
+            Some(told) if told == status => return None,
+            Some((level, score, safeteleports)) if level == self.level => {
+                let mut changes = Vec::new();
+                if score != self.score {
+                    changes.push(format!(
+                        "Score {} (+{}).",
+                        self.score,
+                        self.score.saturating_sub(score)
+                    ));
+                }
+                if safeteleports != self.safeteleports {
+                    changes.push(format!("Safe teleports {}.", self.safeteleports));
+                }
+                changes.join(" ")
+            }
+            _ => format!(
+                "Level {}. Score {}. Safe teleports {}.",
+                self.level, self.score, self.safeteleports
+            ),
+        };
+
+        self.told = Some(status);
+        Some(text)
+    }
+}
+
+impl Renderer for AccessibleBoard {
+    fn init(&mut self) -> Result<(), String> {
+        self.print(&format!(
+            "Escape from evil robots who want to exterminate you. \
+             Type {} for help and {} to describe the board.",
+            self.help_key, self.describe_key
+        ));
+        Ok(())
+    }
+
+    fn close(&mut self) {}
+
+    fn set_status(&mut self, level: u16, score: u64, safeteleports: u16, _seed: u64) {
+        self.level = level + 1;
+        self.score = score;
+        self.safeteleports = safeteleports;
+    }
+
+    fn wants_safe_moves(&self) -> bool {
+        true
+    }
+
+    fn set_safe_moves(&mut self, safe_moves: &[PlayerCommand]) {
+        self.safe_moves = Some(safe_moves.to_vec());
+    }
+
+    fn update(&mut self, arena: &[ObjectType]) {
+        self.arena = arena.to_vec();
+
+        let mut text = String::new();
+        if let Some(changes) = self.status_changes() {
+            text += &changes;
+            text.push('\n');
+        }
+        // the robots moving while waiting are not told one step at a time
+        if let Some(safe_moves) = self.safe_moves.take() {
+            text += &describe_turn(arena, self.arena_width, &safe_moves);
+        }
+
+        self.print(&text);
+    }
+
+    fn show_player_message(&mut self, _x: usize, _y: usize, message: &str) {
+        self.print(message);
+    }
+
+    fn show_message(&mut self, message: &str) {
+        self.print(message);
+    }
+
+    fn show_dialog(&mut self, message: &str) {
+        self.print(message);
+        self.print("(press ENTER)");
+    }
+
+    fn describe(&mut self) {
+        self.print(&describe_board(
+            &self.arena,
+            self.arena_width,
+            self.arena_height,
+        ));
+    }
+
+    // nothing is hidden
+    fn pan(&mut self, _dx: i16, _dy: i16) {}
+
+    // lines scroll, nothing to redraw
+    fn resize(&mut self) -> bool {
+        true
+    }
+}
+
+fn object_name(object: ObjectType) -> &'static str {
+    match object {
+        ObjectType::Robot1 => "robot",
+        ObjectType::Robot2 => "fast robot",
+        ObjectType::Heap => "heap",
+        ObjectType::Player => "you",
+        ObjectType::None => "nothing",
+    }
+}
+
+fn command_name(command: PlayerCommand) -> &'static str {
+    match command {
+        PlayerCommand::NW => "north-west",
+        PlayerCommand::N => "north",
+        PlayerCommand::NE => "north-east",
+        PlayerCommand::W => "west",
+        PlayerCommand::Stay => "stay",
+        PlayerCommand::E => "east",
+        PlayerCommand::SW => "south-west",
+        PlayerCommand::S => "south",
+        PlayerCommand::SE => "south-east",
+        PlayerCommand::SafeTeleport => "safe teleport",
+        PlayerCommand::RandomTeleport => "random teleport",
+        PlayerCommand::Wait => "wait",
+    }
+}
+
+// e.g. "2 north 1 east"
+fn offset_name(dx: isize, dy: isize) -> String {
+    let mut parts = Vec::new();
+    if dy != 0 {
+        let name = if dy < 0 { "north" } else { "south" };
+        parts.push(format!("{} {}", dy.abs(), name));
+    }
+    if dx != 0 {
+        let name = if dx < 0 { "west" } else { "east" };
+        parts.push(format!("{} {}", dx.abs(), name));
+    }
+    parts.join(" ")
+}
+
+fn describe_turn(arena: &[ObjectType], arena_width: usize, safe_moves: &[PlayerCommand]) -> String {
+    let player = match arena
+        .iter()
+        .position(|&object| object == ObjectType::Player)
+    {
+        Some(i) => i,
+        None => return String::new(),
+    };
+    let (player_x, player_y) = (
+        (player % arena_width) as isize,
+        (player / arena_width) as isize,
+    );
+    let arena_height = (arena.len() / arena_width) as isize;
+
+    let mut robots: Vec<(isize, isize, ObjectType)> = arena
+        .iter()
+        .enumerate()
+        .filter(|(_, object)| object.is_robot())
+        .map(|(i, &object)| {
+            let x = (i % arena_width) as isize;
+            let y = (i / arena_width) as isize;
+            (x - player_x, y - player_y, object)
+        })
+        .collect();
+    robots.sort_by_key(|&(dx, dy, _)| (dx.abs().max(dy.abs()), dx.abs() + dy.abs()));
+
+    let mut text = format!(
+        "You are at column {}, row {}. {} robots left.\n",
+        player_x + 1,
+        player_y + 1,
+        robots.len()
+    );
+
+    if !robots.is_empty() {
+        let nearest: Vec<String> = robots
+            .iter()
+            .take(NEAREST_ROBOTS)
+            .map(|&(dx, dy, object)| format!("{} {}", object_name(object), offset_name(dx, dy)))
+            .collect();
+        text += &format!("Nearest: {}.\n", nearest.join(", "));
+    }
+
+    // the robots can reach you, the heaps block you (or can be pushed)
+    let mut next_to = Vec::new();
+    for command in [
+        PlayerCommand::NW,
+        PlayerCommand::N,
+        PlayerCommand::NE,
+        PlayerCommand::W,
+        PlayerCommand::E,
+        PlayerCommand::SW,
+        PlayerCommand::S,
+        PlayerCommand::SE,
+    ] {
+        let (dx, dy) = command.to_direction();
+        let (x, y) = (player_x + dx as isize, player_y + dy as isize);
+        let object = if x < 0 || y < 0 || x >= arena_width as isize || y >= arena_height {
+            "wall"
+        } else {
+            match arena[x as usize + y as usize * arena_width] {
+                ObjectType::None | ObjectType::Player => continue,
+                object => object_name(object),
+            }
+        };
+        next_to.push(format!("{} {}", object, command_name(command)));
+    }
+    if !next_to.is_empty() {
+        text += &format!("Next to you: {}.\n", next_to.join(", "));
+    }
+
+    if safe_moves.is_empty() {
+        text += "No safe moves, teleport!\n";
+    } else {
+        let names: Vec<&str> = safe_moves
+            .iter()
+            .map(|&command| command_name(command))
+            .collect();
+        text += &format!("Safe: {}.\n", names.join(", "));
+    }
+
+    text
+}
+
+// Everything on the board, row by row
+fn describe_board(arena: &[ObjectType], arena_width: usize, arena_height: usize) -> String {
+    let mut text = format!(
+        "The board has {} columns and {} rows.\n",
+        arena_width, arena_height
+    );
+
+    for y in 0..arena_height {
+        let objects: Vec<String> = (0..arena_width)
+            .filter_map(|x| match arena[x + y * arena_width] {
+                ObjectType::None => None,
+                object => Some(format!("{} at column {}", object_name(object), x + 1)),
+            })
+            .collect();
+        if !objects.is_empty() {
+            text += &format!("Row {}: {}.\n", y + 1, objects.join(", "));
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describe() {
+        #[rustfmt::skip]
+        let arena = [
+            ObjectType::None,   ObjectType::None,   ObjectType::None,   ObjectType::None,
+            ObjectType::Player, ObjectType::Heap,   ObjectType::None,   ObjectType::Robot2,
+            ObjectType::None,   ObjectType::Robot1, ObjectType::None,   ObjectType::None,
+        ];
+
+        assert_eq!(
+            describe_turn(&arena, 4, &[PlayerCommand::N, PlayerCommand::NE]),
+            "You are at column 1, row 2. 2 robots left.\n\
+             Nearest: robot 1 south 1 east, fast robot 3 east.\n\
+             Next to you: wall north-west, wall west, heap east, wall south-west, robot south-east.\n\
+             Safe: north, north-east.\n"
+        );
+        assert_eq!(
+            describe_turn(&arena, 4, &[]).lines().last(),
+            Some("No safe moves, teleport!")
+        );
+
+        assert_eq!(
+            describe_board(&arena, 4, 3),
+            "The board has 4 columns and 3 rows.\n\
+             Row 2: you at column 1, heap at column 2, fast robot at column 4.\n\
+             Row 3: robot at column 2.\n"
+        );
+    }
+}
@@ -0,0 +1,267 @@
+use std::cmp::{max, min};
+
+use crate::engine::Engine;
+use crate::enums::*;
+
+const MOVES: [PlayerCommand; 9] = [
+    PlayerCommand::NW,
+    PlayerCommand::N,
+    PlayerCommand::NE,
+    PlayerCommand::W,
+    PlayerCommand::Stay,
+    PlayerCommand::E,
+    PlayerCommand::SW,
+    PlayerCommand::S,
+    PlayerCommand::SE,
+];
+
+const LOOKAHEAD_DEPTH: u32 = 3;
+
+const WON: i64 = i64::MAX;
+const LOST: i64 = i64::MIN;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    // the move to the square farthest from the robots
+    Greedy,
+    // the move that keeps the player alive for the next turns
+    Lookahead,
+}
+
+impl std::str::FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let p = s.to_uppercase();
+        match p.as_str() {
+            "GREEDY" => Ok(Strategy::Greedy),
+            "LOOKAHEAD" => Ok(Strategy::Lookahead),
+            _ => Err(format!("'{}' is not a valid value for Strategy", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Strategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Strategy::Greedy => "GREEDY",
+            Strategy::Lookahead => "LOOKAHEAD",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/*
+ * The command the strategy would give in this situation.
+ * Only moves and waits are simulated: they don't depend on random numbers,
+ * so the strategies can't peek at where a teleport would land.
+ */
+pub fn choose(engine: &Engine, strategy: Strategy) -> PlayerCommand {
+    if safe_to_wait(engine) {
+        return PlayerCommand::Wait;
+    }
+
+    let depth = match strategy {
+        Strategy::Greedy => 1,
+        Strategy::Lookahead => LOOKAHEAD_DEPTH,
+    };
+
+    let mut best_command = None;
+    let mut best_value = LOST;
+    for command in MOVES {
+        if let Some(next) = try_command(engine, command) {
+            let value = search(&next, depth - 1);
+            if best_command.is_none() || value > best_value {
+                best_command = Some(command);
+                best_value = value;
+            }
+        }
+    }
+
+    match best_command {
+        Some(command) if best_value > LOST => command,
+        // every move leads to death (sooner or later): time to teleport,
+        // a safe teleport is refused when there is no safe place left
+        _ if try_command(engine, PlayerCommand::SafeTeleport).is_some() => {
+            PlayerCommand::SafeTeleport
+        }
+        _ => PlayerCommand::RandomTeleport,
+    }
+}
+
+// The moves and waiting that don't kill the player
+pub fn safe_moves(engine: &Engine) -> Vec<PlayerCommand> {
+    let mut safe_moves: Vec<PlayerCommand> = MOVES
+        .into_iter()
+        .filter(|&command| try_command(engine, command).is_some())
+        .collect();
+    if safe_to_wait(engine) {
+        safe_moves.push(PlayerCommand::Wait);
+    }
+    safe_moves
+}
+
+// Where a run (moving on in a direction) stops: at the end of the level,
+// next to a robot or in front of a heap or a wall
+pub fn run_stops(engine: &Engine, direction: PlayerCommand) -> bool {
+    if engine.state() != GameState::Playing {
+        return true;
+    }
+
+    let (x, y) = engine.player_position();
+    let (width, height) = engine.arena_size();
+    let arena = engine.arena();
+    // None outside the arena
+    let near = |(dx, dy): (i16, i16)| {
+        let (x, y) = (x as i16 + dx, y as i16 + dy);
+        if x < 0 || y < 0 || x as usize >= width || y as usize >= height {
+            None
+        } else {
+            Some(arena[x as usize + y as usize * width])
+        }
+    };
+
+    let robot_near = MOVES
+        .into_iter()
+        .any(|command| near(command.to_direction()).is_some_and(|object| object.is_robot()));
+
+    robot_near
+        || matches!(
+            near(direction.to_direction()),
+            None | Some(ObjectType::Heap)
+        )
+}
+
+// The engine after the command, None if the command is refused or kills the player
+fn try_command(engine: &Engine, command: PlayerCommand) -> Option<Engine> {
+    let mut next = engine.clone();
+    let outcome = next.player_command(command);
+
+    if outcome.accepted && outcome.state != GameState::Dead {
+        Some(next)
+    } else {
+        None
+    }
+}
+
+fn safe_to_wait(engine: &Engine) -> bool {
+    let mut next = engine.clone();
+    next.player_command(PlayerCommand::Wait);
+
+    next.finish_waiting().state != GameState::Dead
+}
+
+fn search(engine: &Engine, depth: u32) -> i64 {
+    if engine.state() != GameState::Playing {
+        return WON;
+    }
+
+    if depth == 0 {
+        return evaluate(engine);
+    }
+
+    let mut best = LOST;
+    for command in MOVES {
+        if let Some(next) = try_command(engine, command) {
+            best = max(best, search(&next, depth - 1));
+        }
+    }
+    best
+}
+
+// The farther the nearest robot the better, then the fewer robots
+fn evaluate(engine: &Engine) -> i64 {
+    let (width, _) = engine.arena_size();
+    let (player_x, player_y) = engine.player_position();
+
+    let mut nearest = usize::MAX;
+    let mut robots = 0;
+    for (i, object) in engine.arena().iter().enumerate() {
+        if object.is_robot() {
+            let (x, y) = (i % width, i / width);
+            let distance = max(x.abs_diff(player_x), y.abs_diff(player_y));
+            nearest = min(nearest, distance);
+            robots += 1;
+        }
+    }
+
+    min(nearest, 10) as i64 * 1000 - robots * 10
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::Options;
+
+    #[test]
+    fn run_away() {
+        for strategy in [Strategy::Greedy, Strategy::Lookahead] {
+            let mut engine = Engine::empty(&Options::default());
+            let (px, py) = engine.player_position();
+            engine.put(px + 2, py, ObjectType::Robot1);
+
+            let command = choose(&engine, strategy);
+
+            assert!(
+                [PlayerCommand::NW, PlayerCommand::W, PlayerCommand::SW].contains(&command),
+                "{:?} moved {:?}",
+                strategy,
+                command
+            );
+        }
+    }
+
+    #[test]
+    fn wait_when_safe() {
+        let mut engine = Engine::empty(&Options::default());
+        let (px, py) = engine.player_position();
+        engine.put(px + 4, py - 1, ObjectType::Robot1);
+        engine.put(px + 4, py + 1, ObjectType::Robot1);
+
+        assert_eq!(choose(&engine, Strategy::Greedy), PlayerCommand::Wait);
+    }
+
+    #[test]
+    fn run_until_something_happens() {
+        let mut engine = Engine::empty(&Options::default());
+        let (px, py) = engine.player_position();
+        engine.put(px + 3, py, ObjectType::Heap);
+        // far away, the level goes on
+        engine.put(0, 0, ObjectType::Robot1);
+
+        assert!(!run_stops(&engine, PlayerCommand::E));
+        engine.player_command(PlayerCommand::E);
+        engine.player_command(PlayerCommand::E);
+        // in front of the heap
+        assert!(run_stops(&engine, PlayerCommand::E));
+        assert!(!run_stops(&engine, PlayerCommand::N));
+
+        // next to a robot
+        engine.put(px + 1, py + 1, ObjectType::Robot1);
+        assert!(run_stops(&engine, PlayerCommand::N));
+        assert!(!safe_moves(&engine).contains(&PlayerCommand::SW));
+    }
+
+    #[test]
+    fn teleport_when_trapped() {
+        let mut engine = Engine::empty(&Options::default());
+        let (px, py) = engine.player_position();
+        for (x, y) in [
+            (0, 0),
+            (1, 0),
+            (2, 0),
+            (0, 1),
+            (2, 1),
+            (0, 2),
+            (1, 2),
+            (2, 2),
+        ] {
+            engine.put(px + x - 1, py + y - 1, ObjectType::Robot1);
+        }
+
+        assert_eq!(
+            choose(&engine, Strategy::Lookahead),
+            PlayerCommand::RandomTeleport
+        );
+    }
+}
@@ -1,17 +1,47 @@
-use argparse::{ArgumentParser, StoreFalse, StoreOption, StoreTrue};
+use argparse::{ArgumentParser, Store, StoreConst, StoreOption, StoreTrue};
+use std::path::PathBuf;
 use std::process;
 
+use crate::ai;
 use crate::options;
 use crate::profiles;
+use crate::scores;
+use crate::themes;
 
-pub fn parse_args(options: &mut options::Options) {
-    let mut boardtype: std::option::Option<options::BoardType> = None;
-    let mut profile: std::option::Option<profiles::Profiles> = None;
+pub enum Mode {
+    Play,
+    Replay(String),
+    Bot,
+    Autoplay(ai::Strategy, u64),
+    Simulate(u32, ai::Strategy, std::option::Option<profiles::Profile>),
+}
+
+/*
+ * The options are merged in this order:
+ * defaults, configuration file (unless --defaults), profile, other command line options
+ */
+pub fn parse_args(options: &mut options::Options) -> Mode {
+    let mut listthemes = false;
+    let mut seed: std::option::Option<u64> = None;
+    let mut practice = false;
+    let mut profile: std::option::Option<String> = None;
+    let mut listprofiles = false;
+    let mut rules = Rules::default();
+    let mut settings = Settings::default();
     let mut howtoplay = false;
     let mut default = false;
     let mut save = false;
+    let mut config: std::option::Option<String> = None;
+    let mut printconfig = false;
     let mut version = false;
+    let mut highscores = false;
     let mut exterminate = false;
+    let mut replay: std::option::Option<String> = None;
+    let mut bot = false;
+    let mut autoplay: std::option::Option<ai::Strategy> = None;
+    let mut autoplay_delay: u64 = 200;
+    let mut simulate: std::option::Option<u32> = None;
+    let mut strategy = ai::Strategy::Lookahead;
 
     // this block limits scope of borrows by parser.refer() method
     {
@@ -23,15 +53,15 @@ pub fn parse_args(options: &mut options::Options) {
             .add_option(&["--how-to-play"], StoreTrue, "How to play");
 
         parser
-            .refer(&mut options.safe_moves)
+            .refer(&mut settings.safe_moves)
             .add_option(
                 &["-s", "--safe-moves"],
-                StoreTrue,
+                StoreConst(Some(true)),
                 "Prevent accidental moves that result in getting killed",
             )
             .add_option(
                 &["--no-safe-moves"],
-                StoreFalse,
+                StoreConst(Some(false)),
                 "Don't prevent accidental moves that result in getting killed",
             );
 
@@ -40,52 +70,271 @@ pub fn parse_args(options: &mut options::Options) {
             .add_option(
                 &["-p", "--profile"],
                 StoreOption,
-                "Set the game profile (CLASSIC, ROBOTS2, NIGHTMARE, ROBOTS2EASY, CLASSICWITHSAFETELEPORTS)");
+                "Set the game profile (CLASSIC, ROBOTS2, NIGHTMARE, ROBOTS2EASY, CLASSICWITHSAFETELEPORTS or a user profile)");
+
+        parser.refer(&mut listprofiles).add_option(
+            &["--list-profiles"],
+            StoreTrue,
+            "Show the built-in and user profiles (in ~/.config/daleks/profiles) and exit",
+        );
+
+        parser.refer(&mut rules.initial_type1).add_option(
+            &["--initial-type1"],
+            StoreOption,
+            "Rule: robots of type 1 (+) on the first level",
+        );
+
+        parser.refer(&mut rules.initial_type2).add_option(
+            &["--initial-type2"],
+            StoreOption,
+            "Rule: robots of type 2 (#) on the first level",
+        );
+
+        parser.refer(&mut rules.increment_type1).add_option(
+            &["--increment-type1"],
+            StoreOption,
+            "Rule: more robots of type 1 on every level",
+        );
+
+        parser.refer(&mut rules.increment_type2).add_option(
+            &["--increment-type2"],
+            StoreOption,
+            "Rule: more robots of type 2 on every level",
+        );
+
+        parser.refer(&mut rules.maximum_type1).add_option(
+            &["--maximum-type1"],
+            StoreOption,
+            "Rule: most robots of type 1 on a level",
+        );
+
+        parser.refer(&mut rules.maximum_type2).add_option(
+            &["--maximum-type2"],
+            StoreOption,
+            "Rule: most robots of type 2 on a level",
+        );
+
+        parser.refer(&mut rules.score_type1).add_option(
+            &["--score-type1"],
+            StoreOption,
+            "Rule: points for a robot of type 1 destroyed",
+        );
+
+        parser.refer(&mut rules.score_type2).add_option(
+            &["--score-type2"],
+            StoreOption,
+            "Rule: points for a robot of type 2 destroyed",
+        );
+
+        parser.refer(&mut rules.score_type1_waiting).add_option(
+            &["--score-type1-waiting"],
+            StoreOption,
+            "Rule: points for a robot of type 1 destroyed while waiting",
+        );
+
+        parser.refer(&mut rules.score_type2_waiting).add_option(
+            &["--score-type2-waiting"],
+            StoreOption,
+            "Rule: points for a robot of type 2 destroyed while waiting",
+        );
+
+        parser.refer(&mut rules.score_type1_splatted).add_option(
+            &["--score-type1-splatted"],
+            StoreOption,
+            "Rule: points for a robot of type 1 splatted by a pushed heap",
+        );
+
+        parser.refer(&mut rules.score_type2_splatted).add_option(
+            &["--score-type2-splatted"],
+            StoreOption,
+            "Rule: points for a robot of type 2 splatted by a pushed heap",
+        );
+
+        parser.refer(&mut rules.num_robots_per_safe).add_option(
+            &["--num-robots-per-safe"],
+            StoreOption,
+            "Rule: robots destroyed while waiting for a safe teleport (0 for none)",
+        );
+
+        parser.refer(&mut rules.safe_score_boundary).add_option(
+            &["--safe-score-boundary"],
+            StoreOption,
+            "Rule: points earned while waiting for a safe teleport (0 for none)",
+        );
+
+        parser.refer(&mut rules.initial_safe_teleports).add_option(
+            &["--initial-safe-teleports"],
+            StoreOption,
+            "Rule: safe teleports at the start",
+        );
+
+        parser.refer(&mut rules.free_safe_teleports).add_option(
+            &["--free-safe-teleports"],
+            StoreOption,
+            "Rule: free safe teleports on every level",
+        );
+
+        parser.refer(&mut rules.max_safe_teleports).add_option(
+            &["--max-safe-teleports"],
+            StoreOption,
+            "Rule: most safe teleports you can have",
+        );
+
+        parser
+            .refer(&mut rules.moveable_heaps)
+            .add_option(
+                &["--moveable-heaps"],
+                StoreConst(Some(true)),
+                "Rule: heaps can be pushed",
+            )
+            .add_option(
+                &["--no-moveable-heaps"],
+                StoreConst(Some(false)),
+                "Rule: heaps can't be pushed",
+            );
 
         parser
-            .refer(&mut options.colors)
-            .add_option(&["-c", "--colors"], StoreTrue, "Enable terminal colors")
-            .add_option(&["--no-colors"], StoreFalse, "Disable terminal colors");
+            .refer(&mut settings.colors)
+            .add_option(
+                &["-c", "--colors"],
+                StoreConst(Some(true)),
+                "Enable terminal colors",
+            )
+            .add_option(
+                &["--no-colors"],
+                StoreConst(Some(false)),
+                "Disable terminal colors",
+            );
 
         parser
-            .refer(&mut options.asciionly)
+            .refer(&mut settings.asciionly)
             .add_option(
                 &["-a", "--asciionly"],
-                StoreTrue,
+                StoreConst(Some(true)),
                 "Use only ascii characters",
             )
             .add_option(
                 &["--no-asciionly"],
-                StoreFalse,
+                StoreConst(Some(false)),
                 "Use extended unicode characters",
             );
 
-        parser.refer(&mut boardtype).add_option(
+        parser.refer(&mut settings.theme).add_option(
+            &["--theme"],
+            StoreOption,
+            "Set the look of the board (CLASSIC, BSD, HIGHCONTRAST, EMOJI, DEUTERANOPIA, PROTANOPIA, TRITANOPIA, MONOCHROME or a user theme)",
+        );
+
+        parser.refer(&mut listthemes).add_option(
+            &["--list-themes"],
+            StoreTrue,
+            "Show the built-in and user themes (in ~/.config/daleks/themes) and exit",
+        );
+
+        parser.refer(&mut settings.boardtype).add_option(
             &["-b", "--boardtype"],
             StoreOption,
             "Set the board layout (NORMAL, BSD)",
         );
 
+        parser.refer(&mut settings.boardsize).add_option(
+            &["--boardsize"],
+            StoreOption,
+            "Set the board size (SMALL, NORMAL, LARGE, HUGE, AUTO to fit the terminal, or WIDTHxHEIGHT)",
+        );
+
+        parser.refer(&mut settings.renderer).add_option(
+            &["-r", "--renderer"],
+            StoreOption,
+            "Set the front-end (TERMINAL, TEXT for pipes and dumb terminals, ACCESSIBLE for screen readers)",
+        );
+
+        parser.refer(&mut seed).add_option(
+            &["--seed"],
+            StoreOption,
+            "Set the random seed of the first game (to play again the same game)",
+        );
+
+        parser.refer(&mut practice).add_option(
+            &["--practice"],
+            StoreTrue,
+            "Practice mode: moves can be undone (z) and redone (x), scores don't count",
+        );
+
+        parser.refer(&mut replay).add_option(
+            &["--replay"],
+            StoreOption,
+            "Replay a recorded game (recordings are in ~/.local/share/daleks/recordings)",
+        );
+
         parser.refer(&mut exterminate).add_option(
             &["-x", "--exterminate"],
             StoreTrue,
             "Use at your own risk",
         );
 
+        parser.refer(&mut bot).add_option(
+            &["--bot"],
+            StoreTrue,
+            "Play a game through a JSON protocol on stdin/stdout (for bots)",
+        );
+
+        parser.refer(&mut autoplay).add_option(
+            &["--autoplay"],
+            StoreOption,
+            "Let the computer play with a strategy (GREEDY, LOOKAHEAD)",
+        );
+
+        parser.refer(&mut autoplay_delay).add_option(
+            &["--autoplay-delay"],
+            Store,
+            "Milliseconds between two moves of the computer (default 200)",
+        );
+
+        parser.refer(&mut simulate).add_option(
+            &["--simulate"],
+            StoreOption,
+            "Let the computer play N games per profile without a terminal and show the stats",
+        );
+
+        parser.refer(&mut strategy).add_option(
+            &["--strategy"],
+            Store,
+            "Strategy of the computer for --simulate (GREEDY, LOOKAHEAD, default LOOKAHEAD)",
+        );
+
+        parser.refer(&mut highscores).add_option(
+            &["--scores"],
+            StoreTrue,
+            "Show the high scores (entered with the login name) and exit",
+        );
+
         parser.refer(&mut version).add_option(
             &["--version"],
             StoreTrue,
             "Output version information and exit",
         );
 
-        parser
-            .refer(&mut default)
-            .add_option(&["--defaults"], StoreTrue, "Restore default values");
+        parser.refer(&mut default).add_option(
+            &["--defaults"],
+            StoreTrue,
+            "Start from the default values instead of the configuration (also when it is not valid)",
+        );
         parser.refer(&mut save).add_option(
             &["--save-conf"],
             StoreTrue,
             "Save current configuration",
         );
+        parser.refer(&mut config).add_option(
+            &["--config"],
+            StoreOption,
+            "Load and save the configuration from this file (default ~/.config/daleks/config.json)",
+        );
+        parser.refer(&mut printconfig).add_option(
+            &["--print-config"],
+            StoreTrue,
+            "Print the configuration in use (defaults, file, profile and options) and exit",
+        );
 
         parser.parse_args_or_exit();
     }
@@ -106,20 +355,215 @@ pub fn parse_args(options: &mut options::Options) {
         process::exit(0);
     }
 
-    if let Some(boardtype) = boardtype {
-        options.boardtype = boardtype;
+    if highscores {
+        let highscores = scores::HighScores::load().unwrap_or_else(|e| crate::exit_with_error(&e));
+        print!("{}", highscores.format_all());
+        process::exit(0);
+    }
+
+    if listprofiles {
+        print!("{}", profiles::format_list());
+        process::exit(0);
+    }
+
+    if listthemes {
+        print!("{}", themes::format_list());
+        process::exit(0);
+    }
+
+    let config_path = config.map_or_else(options::Options::default_path, PathBuf::from);
+
+    *options = if default {
+        options::Options::default()
+    } else {
+        options::Options::load(&config_path).unwrap_or_else(|e| {
+            crate::exit_with_error(&format!(
+                "Invalid configuration {}\nFix it or use --defaults to start with the default values (--defaults --save-conf overwrites it)",
+                e
+            ))
+        })
+    };
+
+    let profile =
+        profile.map(|name| profiles::find(&name).unwrap_or_else(|e| crate::exit_with_error(&e)));
+
+    merge(options, profile.as_ref(), &rules, &settings);
+    options.seed = seed;
+    options.practice = practice;
+
+    let errors = options.validate();
+    if !errors.is_empty() {
+        crate::exit_with_error(&errors.join("\n"));
+    }
+
+    if save {
+        options
+            .store(&config_path)
+            .unwrap_or_else(|e| crate::exit_with_error(&e));
+    }
+
+    if printconfig {
+        println!("{}", options.serialize_config());
+        process::exit(0);
+    }
+
+    options.fit_to_terminal();
+
+    if let Some(games) = simulate {
+        // all the profiles, unless some rules are chosen
+        let rules = if profile.is_some() || rules != Rules::default() {
+            Some(options.profile())
+        } else {
+            None
+        };
+        return Mode::Simulate(games, strategy, rules);
+    }
+
+    if bot {
+        return Mode::Bot;
     }
 
+    if let Some(strategy) = autoplay {
+        return Mode::Autoplay(strategy, autoplay_delay);
+    }
+
+    match replay {
+        Some(path) => Mode::Replay(path),
+        None => Mode::Play,
+    }
+}
+
+// The profile over the loaded options, the command line over both
+fn merge(
+    options: &mut options::Options,
+    profile: std::option::Option<&profiles::Profile>,
+    rules: &Rules,
+    settings: &Settings,
+) {
     if let Some(profile) = profile {
         options.set_profile(profile);
     }
+    rules.apply(options);
+    settings.apply(options);
+}
+
+// The rules given on the command line, over the ones of the profile
+#[derive(Default, PartialEq, Eq)]
+struct Rules {
+    initial_type1: std::option::Option<u16>,
+    initial_type2: std::option::Option<u16>,
+    increment_type1: std::option::Option<u16>,
+    increment_type2: std::option::Option<u16>,
+    maximum_type1: std::option::Option<u16>,
+    maximum_type2: std::option::Option<u16>,
+    score_type1: std::option::Option<u64>,
+    score_type2: std::option::Option<u64>,
+    score_type1_waiting: std::option::Option<u64>,
+    score_type2_waiting: std::option::Option<u64>,
+    score_type1_splatted: std::option::Option<u64>,
+    score_type2_splatted: std::option::Option<u64>,
+    num_robots_per_safe: std::option::Option<u16>,
+    safe_score_boundary: std::option::Option<u64>,
+    initial_safe_teleports: std::option::Option<u16>,
+    free_safe_teleports: std::option::Option<u16>,
+    max_safe_teleports: std::option::Option<u16>,
+    moveable_heaps: std::option::Option<bool>,
+}
 
-    if default {
-        *options = options::Options::default();
+impl Rules {
+    fn apply(&self, options: &mut options::Options) {
+        if let Some(value) = self.initial_type1 {
+            options.initial_type1 = value;
+        }
+        if let Some(value) = self.initial_type2 {
+            options.initial_type2 = value;
+        }
+        if let Some(value) = self.increment_type1 {
+            options.increment_type1 = value;
+        }
+        if let Some(value) = self.increment_type2 {
+            options.increment_type2 = value;
+        }
+        if let Some(value) = self.maximum_type1 {
+            options.maximum_type1 = value;
+        }
+        if let Some(value) = self.maximum_type2 {
+            options.maximum_type2 = value;
+        }
+        if let Some(value) = self.score_type1 {
+            options.score_type1 = value;
+        }
+        if let Some(value) = self.score_type2 {
+            options.score_type2 = value;
+        }
+        if let Some(value) = self.score_type1_waiting {
+            options.score_type1_waiting = value;
+        }
+        if let Some(value) = self.score_type2_waiting {
+            options.score_type2_waiting = value;
+        }
+        if let Some(value) = self.score_type1_splatted {
+            options.score_type1_splatted = value;
+        }
+        if let Some(value) = self.score_type2_splatted {
+            options.score_type2_splatted = value;
+        }
+        if let Some(value) = self.num_robots_per_safe {
+            options.num_robots_per_safe = value;
+        }
+        if let Some(value) = self.safe_score_boundary {
+            options.safe_score_boundary = value;
+        }
+        if let Some(value) = self.initial_safe_teleports {
+            options.initial_safe_teleports = value;
+        }
+        if let Some(value) = self.free_safe_teleports {
+            options.free_safe_teleports = value;
+        }
+        if let Some(value) = self.max_safe_teleports {
+            options.max_safe_teleports = value;
+        }
+        if let Some(value) = self.moveable_heaps {
+            options.moveable_heaps = value;
+        }
     }
+}
 
-    if save {
-        options.store();
+// The other options given on the command line
+#[derive(Default)]
+struct Settings {
+    boardtype: std::option::Option<options::BoardType>,
+    boardsize: std::option::Option<options::BoardSize>,
+    renderer: std::option::Option<options::RendererType>,
+    safe_moves: std::option::Option<bool>,
+    colors: std::option::Option<bool>,
+    asciionly: std::option::Option<bool>,
+    theme: std::option::Option<String>,
+}
+
+impl Settings {
+    fn apply(&self, options: &mut options::Options) {
+        if let Some(boardtype) = self.boardtype {
+            options.boardtype = boardtype;
+        }
+        if let Some(boardsize) = self.boardsize {
+            options.boardsize = boardsize;
+        }
+        if let Some(renderer) = self.renderer {
+            options.renderer = renderer;
+        }
+        if let Some(safe_moves) = self.safe_moves {
+            options.safe_moves = safe_moves;
+        }
+        if let Some(colors) = self.colors {
+            options.colors = colors;
+        }
+        if let Some(asciionly) = self.asciionly {
+            options.asciionly = asciionly;
+        }
+        if let Some(theme) = &self.theme {
+            options.theme = theme.clone();
+        }
     }
 }
 
@@ -144,3 +588,47 @@ const EXTERMINATE: &str = "
        (/() / ()    |  ()   | ()  |
       /===========================|
 ";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profiles::{Profile, Profiles};
+    use std::fs;
+
+    #[test]
+    fn merge_order() {
+        // the configuration file changes some rules and settings
+        let dir = std::env::temp_dir().join(format!("daleks-argparse-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.json");
+        let mut stored = options::Options::default();
+        stored.initial_type2 = 5;
+        stored.colors = false;
+        stored.boardtype = options::BoardType::Bsd;
+        stored.store(&path).unwrap();
+        let mut options = options::Options::load(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        // -p CLASSIC --initial-type1 30 --boardtype NORMAL
+        let classic = Profile::get_profile(Profiles::Classic);
+        let rules = Rules {
+            initial_type1: Some(30),
+            ..Rules::default()
+        };
+        let settings = Settings {
+            boardtype: Some(options::BoardType::Normal),
+            ..Settings::default()
+        };
+        merge(&mut options, Some(&classic), &rules, &settings);
+
+        assert_eq!(
+            options.profile(),
+            Profile {
+                initial_type1: 30,
+                ..classic
+            }
+        );
+        assert!(!options.colors);
+        assert_eq!(options.boardtype, options::BoardType::Normal);
+    }
+}
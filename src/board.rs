@@ -1,228 +1,214 @@
-use std::cmp::max;
-use std::io::{stdin, stdout, Stdout, Write};
+use std::cmp::{max, min};
+use std::io::{stdout, Stdout, Write};
 
 use termion::color;
 use termion::cursor;
-use termion::event::Key;
-use termion::input::TermRead;
 use termion::raw::IntoRawMode;
 use termion::raw::RawTerminal;
-// use termion::screen;
+use termion::screen::{AlternateScreen, IntoAlternateScreen};
+use termion::style;
 use termion::terminal_size;
 
-use crate::enums::ObjectType;
-use crate::options::BoardSize;
+use crate::enums::{ObjectType, PlayerCommand};
+use crate::keys::{self, KeyBindings};
 use crate::options::BoardType;
 use crate::options::Options;
+use crate::options::MIN_BOARD_SIZE;
+use crate::renderer::Renderer;
+use crate::themes::{self, ColorDepth, Style, Theme, Themes};
 
 pub struct Board {
     // Immutable fields
-    board_width: usize,
-    board_height: usize,
     boardtype: BoardType,
-    //stdout: screen::AlternateScreen<RawTerminal<Stdout>>,
-    stdout: RawTerminal<Stdout>,
-    asciionly: bool,
-    colors: bool,
+    // dropping it goes back to the main screen and the cooked mode
+    stdout: AlternateScreen<RawTerminal<Stdout>>,
+    theme: Theme,
+    // told in the BSD sidebar
+    keys: KeyBindings,
+    // the escape sequences of the colors of the theme, empty without colors
+    inks: [String; INKS],
+    background: String,
+    // the columns of a cell of the arena
+    cell_width: usize,
+
+    // the whole board and arena, the terminal can show only a part of them
+    full_board_width: usize,
+    full_board_height: usize,
+    field_width: usize,
+    field_height: usize,
 
     // Derived fields
-    delta_x: usize,
-    delta_y: usize,
-
+    // what fits in the terminal, the arena in columns and rows
+    board_width: usize,
+    board_height: usize,
     arena_width: usize,
     arena_height: usize,
 
+    // the part of the arena on screen
+    view: Viewport,
+
     // Runtime fields
+    screen: Screen,
+    covered: bool,
+    // what is on screen, to draw it again after a resize
+    arena: Vec<ObjectType>,
+    player_message: Option<(usize, usize, String)>,
+    dialog: Option<(String, bool)>,
     level: u16,
     score: u64,
     safeteleports: u16,
+    seed: u64,
 }
 
 impl Board {
-    pub fn new(options: &Options) -> Self {
-        let board_width;
-        let board_height;
-
-        match options.boardsize {
-            BoardSize::Normal => {
-                board_width = 80;
-                board_height = 24;
-            }
+    pub fn new(options: &Options) -> Result<Self, String> {
+        let (board_width, board_height) = options.board_size();
+        let (arena_width, arena_height) = options.arena_size();
+
+        // a theme that is gone (e.g. from another computer) is no reason not to play
+        let mut theme = themes::find(&options.theme).unwrap_or_else(|e| {
+            crate::warn(&format!("{}, the classic theme is used", e));
+            Theme::get_theme(Themes::Classic)
+        });
+        if options.asciionly {
+            theme = theme.to_ascii();
         }
 
-        let arena_width;
-        let arena_height;
-
-        match options.boardtype {
-            BoardType::Normal => {
-                arena_width = board_width - 2;
-                arena_height = board_height - 3;
+        let depth = ColorDepth::detect();
+        let background = match theme.background {
+            Some(background) if options.colors => background.bg(depth),
+            _ => String::new(),
+        };
+        // the styles are shown also without colors
+        let ink = |color: &themes::Color, style: &[Style]| {
+            let mut ink = String::new();
+            if theme.is_styled() {
+                ink.push_str(&format!("{}{}", style::Reset, background));
             }
-            BoardType::Bsd => {
-                arena_width = board_width - 2 - 19;
-                arena_height = board_height - 2;
+            if options.colors {
+                ink.push_str(&color.fg(depth));
             }
-        }
+            for style in style {
+                ink.push_str(&style.escape());
+            }
+            ink
+        };
+        let inks = [
+            ink(&theme.text, &[]),
+            ink(&theme.wall.color, &theme.wall.style),
+            ink(&theme.player.color, &theme.player.style),
+            ink(&theme.robot1.color, &theme.robot1.style),
+            ink(&theme.robot2.color, &theme.robot2.style),
+            ink(&theme.heap.color, &theme.heap.style),
+            ink(&theme.empty.color, &theme.empty.style),
+        ];
+        let cell_width = if theme.wide { 2 } else { 1 };
+
+        let stdout = stdout()
+            .into_raw_mode()
+            .and_then(|stdout| stdout.into_alternate_screen())
+            .map_err(|e| format!("Cannot use the terminal: {}", e))?;
+
+        Ok(Board {
+            boardtype: options.boardtype,
+            stdout,
+            theme,
+            keys: options.keys.clone(),
+            inks,
+            background,
+            cell_width,
+
+            full_board_width: board_width,
+            full_board_height: board_height,
+            field_width: arena_width,
+            field_height: arena_height,
 
-        Board {
             board_width,
             board_height,
-            boardtype: options.boardtype,
-            //stdout: screen::AlternateScreen::from(stdout().into_raw_mode().unwrap()),
-            stdout: stdout().into_raw_mode().unwrap(),
-            asciionly: options.asciionly,
-            colors: options.colors,
-
-            delta_x: 0,
-            delta_y: 0,
-
             arena_width,
             arena_height,
 
+            view: Viewport::new(arena_width, arena_height),
+
+            screen: Screen::new(0, 0, 0, 0),
+            covered: false,
+            arena: Vec::new(),
+            player_message: None,
+            dialog: None,
             level: 0,
             score: 0,
             safeteleports: 0,
-        }
-    }
-
-    pub fn close(&mut self) {
-        write!(self.stdout, "\n\n\r").unwrap();
-        write!(self.stdout, "{}", termion::cursor::Show).unwrap();
+            seed: 0,
+        })
     }
 
-    pub fn init(&mut self) -> Result<(), String> {
-        let (terminal_width, terminal_height) = terminal_size().unwrap();
-        let terminal_width = terminal_width as usize;
-        let terminal_height = terminal_height as usize;
-
-        if terminal_width < self.board_width {
-            return Err(format!(
-                "Terminal width is too small for board ({} < {})",
-                terminal_width, self.board_width
-            ));
-        }
-        if terminal_height < self.board_height {
-            return Err(format!(
-                "Terminal height is too small for board ({} < {})",
-                terminal_height, self.board_height
-            ));
-        }
-
-        self.delta_x = (terminal_width - self.board_width) / 2;
-        self.delta_y = (terminal_height - self.board_height) / 2;
-
-        write!(self.stdout, "{}", termion::clear::All).unwrap();
-        write!(self.stdout, "{}", termion::cursor::Hide).unwrap();
-
-        // let backgroundcolor = color::Bg(color::Rgb(19, 25, 38));
-        // write!(self.stdout, "{}{}", backgroundcolor, termion::clear::All).unwrap();
-        // write!(self.stdout, "{}{}", color::Bg(color::Blue), termion::clear::All).unwrap();
-
-        if self.colors {
-            write!(self.stdout, "{}", color::Bg(color::Black)).unwrap();
-        }
-
-        // Clear background
-        for x in 0..self.board_width {
-            for y in 0..self.board_height {
-                self.write_at(x, y, " ", &color::Reset);
+    fn draw_walls(&mut self) {
+        self.draw_borders(0, 0, self.arena_width + 2, self.arena_height + 2, Ink::Wall);
+        if self.boardtype == BoardType::Bsd {
+            let x = self.arena_width + 3;
+            self.write_at(x, 0, "Directions:", Ink::Text);
+            for (i, line) in self.keys.short_compass().iter().enumerate() {
+                self.write_at(x, 2 + i, line, Ink::Text);
             }
-        }
 
-        self.draw_walls();
-        self.draw_status();
-        // self.draw_arena();
-
-        self.stdout.flush().unwrap();
+            let mut l = 8;
+            self.write_at(x, l, "Commands:", Ink::Text);
+            let commands = [
+                (&self.keys.wait, "wait for end"),
+                (&self.keys.safe_teleport, "safe teleport"),
+                (&self.keys.random_teleport, "random teleport"),
+                (&self.keys.quit, "quit"),
+            ]
+            .map(|(keys, command)| format!("{}: {}", keys::main_key(keys), command));
+            for (i, line) in commands.iter().enumerate() {
+                // a long key name is cut at the right border
+                let line: String = line.chars().take(SIDEBAR_WIDTH).collect();
+                self.write_at(x, l + 2 + i, &line, Ink::Text);
+            }
 
-        Ok(())
+            l = 15;
+            let cell = self.cell_width;
+            self.write_at(x, l, "Legend:", Ink::Text);
+            self.draw_glyph(x, l + 2, ObjectType::Player);
+            self.write_at(x + cell, l + 2, "; you", Ink::Text);
+            self.draw_glyph(x, l + 3, ObjectType::Robot1);
+            self.draw_glyph(x + cell + 1, l + 3, ObjectType::Robot2);
+            self.write_at(x + 2 * cell + 1, l + 3, ": robot", Ink::Text);
+            self.draw_glyph(x, l + 4, ObjectType::Heap);
+            self.write_at(x + cell, l + 4, ": heap", Ink::Text);
+        }
     }
 
-    fn draw_walls(&mut self) {
-        self.draw_borders(
-            0,
-            0,
-            self.arena_width + 2,
-            self.arena_height + 2,
-            COLOR_WALL,
+    fn draw_borders(&mut self, x: usize, y: usize, width: usize, height: usize, ink: Ink) {
+        let glyphs: Vec<char> = self.theme.wall.glyph.chars().collect();
+        let (nwcorner, necorner, swcorner, secorner, hwall, vwall) = (
+            glyphs[0].to_string(),
+            glyphs[1].to_string(),
+            glyphs[2].to_string(),
+            glyphs[3].to_string(),
+            glyphs[4].to_string(),
+            glyphs[5].to_string(),
         );
-        if self.boardtype == BoardType::Bsd {
-            self.write_at(self.arena_width + 3, 0, "Directions:", COLOR_TEXT);
-            self.write_at(self.arena_width + 3, 2, "y k u", COLOR_TEXT);
-            self.write_at(self.arena_width + 3, 3, " \\!/", COLOR_TEXT);
-            self.write_at(self.arena_width + 3, 4, "h- -l", COLOR_TEXT);
-            self.write_at(self.arena_width + 3, 5, " /|\\", COLOR_TEXT);
-            self.write_at(self.arena_width + 3, 6, "b j n", COLOR_TEXT);
-
-            let mut l = 8;
-            self.write_at(self.arena_width + 3, l, "Commands:", COLOR_TEXT);
-            self.write_at(self.arena_width + 3, l + 2, "w: wait for end", COLOR_TEXT);
-            self.write_at(self.arena_width + 3, l + 3, "+: safe teleport", COLOR_TEXT);
-            self.write_at(
-                self.arena_width + 3,
-                l + 4,
-                "-: random teleport",
-                COLOR_TEXT,
-            );
-            self.write_at(self.arena_width + 3, l + 5, "q: quit", COLOR_TEXT);
 
-            l = 15;
-            self.write_at(self.arena_width + 3, l, "Legend:", COLOR_TEXT);
-            self.write_at(self.arena_width + 3, l + 2, "@; you", COLOR_TEXT);
-            self.write_at(self.arena_width + 3, l + 3, "+ #: robot", COLOR_TEXT);
-            self.write_at(self.arena_width + 3, l + 4, "*: heap", COLOR_TEXT);
-        }
-    }
-
-    fn draw_borders(
-        &mut self,
-        x: usize,
-        y: usize,
-        width: usize,
-        height: usize,
-        color: &dyn color::Color,
-    ) {
-        let nwcorner;
-        let necorner;
-        let swcorner;
-        let secorner;
-        let hwall;
-        let vwall;
-        if self.asciionly {
-            nwcorner = "+";
-            necorner = "+";
-            swcorner = "+";
-            secorner = "+";
-            hwall = "-";
-            vwall = "|";
-        } else {
-            nwcorner = "╔";
-            necorner = "╗";
-            swcorner = "╚";
-            secorner = "╝";
-            hwall = "═";
-            vwall = "║";
-        }
-        self.write_at(x, y, nwcorner, color);
-        self.write_at(x + width - 1, y, necorner, color);
-        self.write_at(x, y + height - 1, swcorner, color);
-        self.write_at(x + width - 1, y + height - 1, secorner, color);
+        self.write_at(x, y, &nwcorner, ink);
+        self.write_at(x + width - 1, y, &necorner, ink);
+        self.write_at(x, y + height - 1, &swcorner, ink);
+        self.write_at(x + width - 1, y + height - 1, &secorner, ink);
         for x1 in 1..width - 1 {
-            self.write_at(x + x1, y, hwall, color);
-            self.write_at(x + x1, y + height - 1, hwall, color);
+            self.write_at(x + x1, y, &hwall, ink);
+            self.write_at(x + x1, y + height - 1, &hwall, ink);
         }
         for y1 in 1..height - 1 {
-            self.write_at(x, y + y1, vwall, color);
-            self.write_at(x + width - 1, y + y1, vwall, color);
+            self.write_at(x, y + y1, &vwall, ink);
+            self.write_at(x + width - 1, y + y1, &vwall, ink);
         }
-
-        self.stdout.flush().unwrap();
     }
 
     fn draw_status(&mut self) {
         let level = format!("{}: {}", "Level", self.level);
         let score = format!("{}: {}", "Score", self.score);
         let safes = format!("{}: {}", "Safe teleports", self.safeteleports);
+        let seed = format!("{}: {}", "Seed", self.seed);
 
         match self.boardtype {
             BoardType::Normal => {
@@ -230,120 +216,396 @@ impl Board {
 
                 let level_len = level.chars().count();
                 let score_len = score.chars().count();
+                let seed_len = seed.chars().count();
+                let safes_len = safes.chars().count();
 
-                let score_padding = status_len / 2 - level_len + score_len / 2;
-                let safes_padding = status_len - score_padding - level_len;
+                let padding =
+                    status_len.saturating_sub(level_len + score_len + seed_len + safes_len);
+                let score_padding = padding / 3 + score_len;
+                let seed_padding = padding / 3 + seed_len;
+                let safes_padding = padding - 2 * (padding / 3) + safes_len;
 
                 let status_line = format!(
-                    " {}{:>score_padding$}{:>safes_padding$} ",
+                    " {}{:>score_padding$}{:>seed_padding$}{:>safes_padding$} ",
                     level,
                     score,
+                    seed,
                     safes,
                     score_padding = score_padding,
+                    seed_padding = seed_padding,
                     safes_padding = safes_padding
                 );
 
-                self.write_at(0, self.board_height - 1, &status_line, COLOR_TEXT);
+                self.write_at(0, self.board_height - 1, &status_line, Ink::Text);
             }
             BoardType::Bsd => {
+                let seed = format!(
+                    "{:<width$}",
+                    seed,
+                    width = self.board_width - self.arena_width - 3
+                );
+                self.write_at(
+                    self.arena_width + 3,
+                    self.board_height - 4,
+                    &seed,
+                    Ink::Text,
+                );
                 self.write_at(
                     self.arena_width + 3,
                     self.board_height - 3,
                     &level,
-                    COLOR_TEXT,
+                    Ink::Text,
                 );
                 self.write_at(
                     self.arena_width + 3,
                     self.board_height - 2,
                     &score,
-                    COLOR_TEXT,
+                    Ink::Text,
                 );
                 self.write_at(
                     self.arena_width + 3,
                     self.board_height - 1,
                     &safes,
-                    COLOR_TEXT,
+                    Ink::Text,
                 );
             }
         }
-
-        self.stdout.flush().unwrap();
     }
 
-    //fn draw_arena(&mut self, arena: &Vec<ObjectType>) {
     fn draw_arena(&mut self, arena: &[ObjectType]) {
-        for x in 0..self.arena_width {
+        for x in 0..self.view.width {
             for y in 0..self.arena_height {
-                let c = match arena[x + y * self.arena_width] {
-                    ObjectType::None => " ",
-                    ObjectType::Player => "@",
-                    ObjectType::Heap => "*",
-                    ObjectType::Robot1 => "+",
-                    ObjectType::Robot2 => "#",
-                };
-                let color: &dyn color::Color = match arena[x + y * self.arena_width] {
-                    ObjectType::None => &color::White, // do not show
-                    ObjectType::Player => COLOR_PLAYER,
-                    ObjectType::Heap => COLOR_HEAP,
-                    ObjectType::Robot1 => COLOR_ROBOT1,
-                    ObjectType::Robot2 => COLOR_ROBOT2,
-                };
+                let i = (x + self.view.x) + (y + self.view.y) * self.field_width;
+                self.draw_glyph(x * self.cell_width + 1, y + 1, arena[i]);
+            }
+        }
+
+        self.draw_minimap(arena);
+    }
+
+    // The glyph of the theme, a wide one takes two columns
+    fn draw_glyph(&mut self, x: usize, y: usize, object: ObjectType) {
+        let (glyph, ink) = match object {
+            ObjectType::Player => (&self.theme.player.glyph, Ink::Player),
+            ObjectType::Robot1 => (&self.theme.robot1.glyph, Ink::Robot1),
+            ObjectType::Robot2 => (&self.theme.robot2.glyph, Ink::Robot2),
+            ObjectType::Heap => (&self.theme.heap.glyph, Ink::Heap),
+            ObjectType::None => (&self.theme.empty.glyph, Ink::Empty),
+        };
+
+        let mut chars = glyph.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if self.cell_width == 2 => {
+                if x + 1 < self.board_width {
+                    self.put(x, y, Cell { c, ink, wide: true });
+                    self.put(
+                        x + 1,
+                        y,
+                        Cell {
+                            c: WIDE_TAIL,
+                            ink,
+                            wide: false,
+                        },
+                    );
+                }
+            }
+            _ => {
+                let glyph = glyph.clone();
+                self.write_at(x, y, &glyph, ink);
+            }
+        }
+    }
+
+    fn follow_player(&mut self, arena: &[ObjectType]) {
+        let i = match arena
+            .iter()
+            .position(|&object| object == ObjectType::Player)
+        {
+            Some(i) => i,
+            None => return,
+        };
+        self.view
+            .follow((i % self.field_width, i / self.field_width));
+    }
 
-                self.write_at(x + 1, y + 1, c, color);
+    /*
+     * A map of the whole arena in a corner (the one far from the player),
+     * each cell shows how many robots are in its part of the arena.
+     * It covers a part of the view, so it is shown only while looking around.
+     */
+    fn draw_minimap(&mut self, arena: &[ObjectType]) {
+        if !self.view.looking {
+            return;
+        }
+
+        let width = min(MINIMAP_WIDTH, self.arena_width / 3);
+        let height = min(MINIMAP_HEIGHT, self.arena_height / 3);
+
+        let player = arena
+            .iter()
+            .position(|&object| object == ObjectType::Player);
+        let (player_x, player_y) = match player {
+            Some(i) => (i % self.field_width, i / self.field_width),
+            None => (0, 0),
+        };
+
+        let x0 = if player_x < self.view.x + self.view.width / 2 {
+            self.arena_width + 1 - (width + 2)
+        } else {
+            1
+        };
+        let y0 = if player_y < self.view.y + self.arena_height / 2 {
+            self.arena_height + 1 - (height + 2)
+        } else {
+            1
+        };
+
+        self.draw_borders(x0, y0, width + 2, height + 2, Ink::Wall);
+
+        for my in 0..height {
+            let mut line = String::new();
+            for mx in 0..width {
+                let (x1, x2) = (
+                    mx * self.field_width / width,
+                    (mx + 1) * self.field_width / width,
+                );
+                let (y1, y2) = (
+                    my * self.field_height / height,
+                    (my + 1) * self.field_height / height,
+                );
+
+                let mut robots = 0;
+                for y in y1..y2 {
+                    for x in x1..x2 {
+                        if arena[x + y * self.field_width].is_robot() {
+                            robots += 1;
+                        }
+                    }
+                }
+                let density = robots * 100 / ((x2 - x1) * (y2 - y1)).max(1);
+
+                let c = if (x1..x2).contains(&player_x) && (y1..y2).contains(&player_y) {
+                    '@'
+                } else if robots == 0 {
+                    ' '
+                } else {
+                    let shades = if self.theme.wall.glyph.is_ascii() {
+                        ['.', ':', '%']
+                    } else {
+                        ['░', '▒', '▓']
+                    };
+                    match density {
+                        0..=4 => shades[0],
+                        5..=14 => shades[1],
+                        _ => shades[2],
+                    }
+                };
+                line.push(c);
             }
+            self.write_at(x0 + 1, y0 + 1 + my, &line, Ink::Text);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.screen.clear();
+    }
+
+    // Draws in the frame, what falls outside the board is cut
+    fn write_at(&mut self, x: usize, y: usize, line: &str, ink: Ink) {
+        for (x, c) in (x..self.board_width).zip(line.chars()) {
+            self.put(
+                x,
+                y,
+                Cell {
+                    c,
+                    ink,
+                    wide: false,
+                },
+            );
         }
+    }
+
+    fn put(&mut self, x: usize, y: usize, cell: Cell) {
+        self.screen.put(x, y, cell);
+    }
 
+    fn present(&mut self) {
+        self.screen
+            .present(&mut self.stdout, &self.inks, &self.background);
+    }
+
+    // Gives back the cursor and the colors
+    fn restore(&mut self) {
+        write!(
+            self.stdout,
+            "{}{}{}{}",
+            color::Fg(color::Reset),
+            color::Bg(color::Reset),
+            style::Reset,
+            cursor::Show
+        )
+        .unwrap();
         self.stdout.flush().unwrap();
     }
 
-    pub fn get_arena_size(&self) -> (usize, usize) {
-        (self.arena_width, self.arena_height)
+    fn draw_dialog(&mut self, message: &str, center: bool) {
+        self.dialog = Some((message.to_string(), center));
+
+        let mut width = 0;
+        let mut height = 0;
+        for line in message.lines() {
+            width = max(width, line.chars().count());
+            height += 1;
+        }
+        width += 2;
+        height += 2;
+
+        if width > self.arena_width || height > self.arena_height {
+            self.covered = true;
+        }
+        // a dialog larger than the arena covers what is beside
+        let x = self.arena_width.saturating_sub(width) / 2 + 1;
+        let y = self.arena_height.saturating_sub(height) / 2 + 1;
+
+        self.draw_borders(x, y, width, height, Ink::Text);
+
+        for (y1, line) in (y + 1..).zip(message.lines()) {
+            let s = if center {
+                format!("{: ^width$}", line, width = width - 2)
+            } else {
+                format!("{: <width$}", line, width = width - 2)
+            };
+            self.write_at(x + 1, y1, s.as_str(), Ink::Text);
+        }
     }
 
-    pub fn set_score(&mut self, score: u64) {
-        self.score = score;
-        self.draw_status();
+    /*
+     * Centers the board in the terminal, a board larger than the terminal
+     * shows only a part of the arena
+     */
+    fn fit(&mut self) -> Result<(), String> {
+        let (terminal_width, terminal_height) =
+            terminal_size().map_err(|e| format!("Cannot get the terminal size: {}", e))?;
+        let terminal_width = terminal_width as usize;
+        let terminal_height = terminal_height as usize;
+
+        // the walls, and the sidebar of the BSD board
+        let frame_width = self.full_board_width - self.field_width;
+        let frame_height = self.full_board_height - self.field_height;
+        let full_width = self.field_width * self.cell_width + frame_width;
+
+        let min_width = min(full_width, MIN_BOARD_SIZE.0);
+        let min_height = min(self.full_board_height, MIN_BOARD_SIZE.1);
+
+        if terminal_width < min_width {
+            return Err(format!(
+                "Terminal width is too small for board ({} < {})",
+                terminal_width, min_width
+            ));
+        }
+        if terminal_height < min_height {
+            return Err(format!(
+                "Terminal height is too small for board ({} < {})",
+                terminal_height, min_height
+            ));
+        }
+
+        let view_width = min(
+            self.field_width,
+            (terminal_width - frame_width) / self.cell_width,
+        );
+        self.arena_width = view_width * self.cell_width;
+        self.arena_height = min(self.field_height, terminal_height - frame_height);
+        self.board_width = self.arena_width + frame_width;
+        self.board_height = self.arena_height + frame_height;
+
+        self.screen = Screen::new(
+            self.board_width,
+            self.board_height,
+            (terminal_width - self.board_width) / 2,
+            (terminal_height - self.board_height) / 2,
+        );
+
+        self.view.resize(view_width, self.arena_height);
+
+        Ok(())
     }
+}
 
-    pub fn set_level(&mut self, level: u16) {
-        self.level = level + 1;
+// Also when a panic unwinds the stack
+impl Drop for Board {
+    fn drop(&mut self) {
+        self.restore();
+    }
+}
+
+impl Renderer for Board {
+    fn init(&mut self) -> Result<(), String> {
+        self.fit()?;
+
+        write!(self.stdout, "{}", termion::clear::All).unwrap();
+        write!(self.stdout, "{}", termion::cursor::Hide).unwrap();
+
+        // let backgroundcolor = color::Bg(color::Rgb(19, 25, 38));
+        // write!(self.stdout, "{}{}", backgroundcolor, termion::clear::All).unwrap();
+        // write!(self.stdout, "{}{}", color::Bg(color::Blue), termion::clear::All).unwrap();
+
+        write!(self.stdout, "{}", self.background).unwrap();
+
+        self.clear();
+        self.draw_walls();
         self.draw_status();
+
+        self.present();
+
+        Ok(())
     }
 
-    pub fn set_safeteleports(&mut self, safeteleports: u16) {
+    fn close(&mut self) {
+        self.restore();
+    }
+
+    fn set_status(&mut self, level: u16, score: u64, safeteleports: u16, seed: u64) {
+        self.level = level + 1;
+        self.score = score;
         self.safeteleports = safeteleports;
+        self.seed = seed;
         self.draw_status();
+        self.present();
     }
 
-    pub fn update(&mut self, arena: &[ObjectType]) {
+    // the player sees them
+    fn wants_safe_moves(&self) -> bool {
+        false
+    }
+
+    fn set_safe_moves(&mut self, _safe_moves: &[PlayerCommand]) {}
+
+    fn update(&mut self, arena: &[ObjectType]) {
+        // the walls under a large dialog
+        if self.covered {
+            self.covered = false;
+            self.clear();
+            self.draw_walls();
+        }
+
+        self.follow_player(arena);
         self.draw_arena(arena);
         self.draw_status();
 
-        self.stdout.flush().unwrap();
-    }
+        self.present();
 
-    fn write_at(&mut self, x: usize, y: usize, line: &str, color: &dyn color::Color) {
-        let x = (x + self.delta_x + 1) as u16;
-        let y = (y + self.delta_y + 1) as u16;
-        if self.colors {
-            write!(
-                self.stdout,
-                "{}{}{}{}",
-                cursor::Goto(x, y),
-                color::Fg(color),
-                line,
-                color::Fg(color::Reset)
-            )
-            .unwrap();
-        } else {
-            write!(self.stdout, "{}{}", cursor::Goto(x, y), line).unwrap();
-        }
+        self.arena = arena.to_vec();
+        self.player_message = None;
+        self.dialog = None;
     }
 
-    pub fn show_player_message(&mut self, mut x: usize, mut y: usize, message: &str) {
+    fn show_player_message(&mut self, mut x: usize, mut y: usize, message: &str) {
+        self.player_message = Some((x, y, message.to_string()));
+
         // adapt coords
-        x += 1;
-        y += 1;
+        x = min(x.saturating_sub(self.view.x), self.view.width - 1) * self.cell_width + 1;
+        y = min(y.saturating_sub(self.view.y), self.arena_height - 1) + 1;
 
         let message_len = message.chars().count();
 
@@ -357,79 +619,500 @@ impl Board {
             x -= x + message_len - self.arena_width;
         }
 
-        self.write_at(x, y, message, COLOR_TEXT);
-        self.stdout.flush().unwrap();
+        self.write_at(x, y, message, Ink::Text);
+        self.present();
     }
 
-    fn draw_dialog(&mut self, message: &str, center: bool) {
-        let mut width = 0;
-        let mut height = 0;
-        for line in message.lines() {
-            width = max(width, line.chars().count());
-            height += 1;
+    fn show_message(&mut self, message: &str) {
+        self.draw_dialog(message, true);
+        self.present();
+    }
+
+    fn show_dialog(&mut self, message: &str) {
+        self.draw_dialog(message, false);
+        self.present();
+    }
+
+    // everything is on the screen
+    fn describe(&mut self) {}
+
+    fn pan(&mut self, dx: i16, dy: i16) {
+        if !self.view.pan(dx, dy) {
+            return;
         }
-        width += 2;
-        height += 2;
-        let x = (self.arena_width - width) / 2 + 1;
-        let y = (self.arena_height - height) / 2 + 1;
 
-        self.draw_borders(x, y, width, height, COLOR_TEXT);
+        let arena = self.arena.clone();
+        self.draw_arena(&arena);
+        self.present();
+    }
 
-        let mut y1 = y + 1;
-        for line in message.lines() {
-            let s = if center {
-                format!("{: ^width$}", line, width = width - 2)
-            } else {
-                format!("{: <width$}", line, width = width - 2)
-            };
-            self.write_at(x + 1, y1, s.as_str(), COLOR_TEXT);
-            y1 += 1;
+    fn resize(&mut self) -> bool {
+        write!(self.stdout, "{}{}", self.background, termion::clear::All).unwrap();
+
+        if let Err(e) = self.fit() {
+            write!(
+                self.stdout,
+                "{}{}\r\nThe game is paused, enlarge the terminal to go on",
+                cursor::Goto(1, 1),
+                e
+            )
+            .unwrap();
+            self.stdout.flush().unwrap();
+            return false;
         }
 
-        self.stdout.flush().unwrap();
+        self.clear();
+        self.draw_walls();
+        self.draw_status();
+
+        if !self.arena.is_empty() {
+            let arena = self.arena.clone();
+            self.draw_arena(&arena);
+        }
+        if let Some((x, y, message)) = self.player_message.clone() {
+            self.show_player_message(x, y, &message);
+        }
+        if let Some((message, center)) = self.dialog.clone() {
+            self.draw_dialog(&message, center);
+        }
+
+        self.present();
+
+        true
     }
+}
 
-    pub fn show_confirmation_dialog(&mut self, message: &str) -> bool {
-        self.draw_dialog(message, true);
+// The view scrolls when the player gets closer than this to a border
+const VIEW_MARGIN: usize = 4;
+
+/*
+ * The part of the arena on screen when it does not fit in the terminal, in cells
+ */
+#[derive(Debug)]
+struct Viewport {
+    // the whole arena
+    field_width: usize,
+    field_height: usize,
+
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    // the view has been moved by hand, it stays there until the player
+    // moves out of it
+    looking: bool,
+    player: Option<(usize, usize)>,
+}
 
-        let stdin = stdin();
+impl Viewport {
+    // All the arena, until it is fitted in the terminal
+    fn new(field_width: usize, field_height: usize) -> Self {
+        Viewport {
+            field_width,
+            field_height,
+
+            x: 0,
+            y: 0,
+            width: field_width,
+            height: field_height,
+            looking: false,
+            player: None,
+        }
+    }
 
-        for c in stdin.keys() {
-            if let Key::Char(c) = c.unwrap() {
-                match c.to_ascii_lowercase() {
-                    'y' => return true,
-                    'q' | 'n' => return false,
-                    _ => {}
-                }
+    fn scrolls(&self) -> bool {
+        self.field_width > self.width || self.field_height > self.height
+    }
+
+    fn contains(&self, (x, y): (usize, usize)) -> bool {
+        (self.x..self.x + self.width).contains(&x) && (self.y..self.y + self.height).contains(&y)
+    }
+
+    // The view stays where it is, as far as the new size allows
+    fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.x = min(self.x, self.field_width - width);
+        self.y = min(self.y, self.field_height - height);
+
+        if let Some(player) = self.player {
+            self.follow(player);
+        }
+    }
+
+    /*
+     * Moves the view so that the player is not too close to its borders,
+     * unless the player is looking around and has not moved out of the view
+     */
+    fn follow(&mut self, player: (usize, usize)) {
+        let moved = self.player != Some(player);
+        self.player = Some(player);
+
+        if self.looking {
+            if self.contains(player) || !moved {
+                return;
             }
+            self.looking = false;
         }
 
-        false
+        self.x = follow(player.0, self.x, self.width, self.field_width);
+        self.y = follow(player.1, self.y, self.height, self.field_height);
     }
 
-    pub fn show_dialog(&mut self, message: &str) {
-        self.draw_dialog(message, false);
+    // Looks a quarter of the view farther, false when the arena does not scroll
+    fn pan(&mut self, dx: i16, dy: i16) -> bool {
+        if !self.scrolls() {
+            return false;
+        }
+
+        let step_x = max(self.width / 4, 1) as isize * dx as isize;
+        let step_y = max(self.height / 4, 1) as isize * dy as isize;
+        self.x =
+            (self.x as isize + step_x).clamp(0, (self.field_width - self.width) as isize) as usize;
+        self.y = (self.y as isize + step_y).clamp(0, (self.field_height - self.height) as isize)
+            as usize;
+        self.looking = true;
+        true
+    }
+}
 
-        let stdin = stdin();
+// The first coordinate of a view keeping the player away from its borders
+fn follow(player: usize, view: usize, view_size: usize, field_size: usize) -> usize {
+    let margin = min(VIEW_MARGIN, view_size / 4);
 
-        // wait for a key press
-        stdin.keys().next();
+    let view = if player < view + margin {
+        player.saturating_sub(margin)
+    } else if player + margin >= view + view_size {
+        player + margin + 1 - view_size
+    } else {
+        view
+    };
+
+    min(view, field_size - view_size)
+}
+
+// What a cell is drawn with, the index of its escape sequence in the inks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Ink {
+    Text,
+    Wall,
+    Player,
+    Robot1,
+    Robot2,
+    Heap,
+    Empty,
+}
+
+const INKS: usize = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cell {
+    c: char,
+    ink: Ink,
+    // the next cell is the tail of this glyph
+    wide: bool,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Cell {
+            c: ' ',
+            ink: Ink::Text,
+            wide: false,
+        }
+    }
+}
+
+// The second column of a wide glyph
+const WIDE_TAIL: char = '\0';
+
+// Unchanged cells written again instead of moving the cursor over them
+const MAX_REWRITE: usize = 4;
+
+/*
+ * The frame being drawn and what the terminal is showing,
+ * only the cells that differ are written
+ */
+struct Screen {
+    width: usize,
+    height: usize,
+    // where the board is in the terminal
+    delta_x: usize,
+    delta_y: usize,
+
+    frame: Vec<Cell>,
+    shown: Vec<Option<Cell>>,
+}
+
+impl Screen {
+    // Nothing is shown yet, the first present writes everything
+    fn new(width: usize, height: usize, delta_x: usize, delta_y: usize) -> Self {
+        Screen {
+            width,
+            height,
+            delta_x,
+            delta_y,
+
+            frame: vec![Cell::default(); width * height],
+            shown: vec![None; width * height],
+        }
+    }
+
+    fn clear(&mut self) {
+        self.frame.fill(Cell::default());
+    }
+
+    fn put(&mut self, x: usize, y: usize, cell: Cell) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+
+        let i = x + y * self.width;
+        // half of a wide glyph does not stay on screen alone
+        if cell.c != WIDE_TAIL {
+            if self.frame[i].c == WIDE_TAIL && x > 0 {
+                self.frame[i - 1] = Cell::default();
+            }
+            if self.frame[i].wide && x + 1 < self.width {
+                self.frame[i + 1] = Cell::default();
+            }
+        }
+        self.frame[i] = cell;
     }
 
     /*
-    pub fn to_mainscreen(&mut self) {
-        write!(self.stdout, "{}", screen::ToMainScreen).unwrap();
+     * Writes the cells of the frame that changed since the last time. The cursor
+     * is moved only to jump over the cells that did not change (short jumps are
+     * cheaper writing the cells again) and the color only when it changes.
+     */
+    fn present(&mut self, terminal: &mut impl Write, inks: &[String; INKS], background: &str) {
+        let mut out = String::new();
+        let mut fg = None;
+
+        for y in 0..self.height {
+            // the column where the cursor is, if on this row
+            let mut cursor_x = None;
+
+            for x in 0..self.width {
+                let i = x + y * self.width;
+                let cell = self.frame[i];
+                // a wide glyph is written again also when only its tail changed
+                let changed = self.shown[i] != Some(cell)
+                    || (cell.wide && self.shown[i + 1] != Some(self.frame[i + 1]));
+                if !changed || cell.c == WIDE_TAIL {
+                    self.shown[i] = Some(cell);
+                    continue;
+                }
+
+                match cursor_x {
+                    Some(cx) if x - cx <= MAX_REWRITE => {
+                        for j in i - (x - cx)..i {
+                            push_cell(&mut out, &mut fg, inks, self.frame[j]);
+                        }
+                    }
+                    _ => out.push_str(
+                        &cursor::Goto((x + self.delta_x + 1) as u16, (y + self.delta_y + 1) as u16)
+                            .to_string(),
+                    ),
+                }
+                push_cell(&mut out, &mut fg, inks, cell);
+                self.shown[i] = Some(cell);
+                cursor_x = Some(x + if cell.wide { 2 } else { 1 });
+            }
+        }
+
+        if out.is_empty() {
+            return;
+        }
+        if fg.is_some() {
+            out.push_str(&format!("{}{}", style::Reset, background));
+        }
+        terminal.write_all(out.as_bytes()).unwrap();
+        terminal.flush().unwrap();
     }
+}
 
-    pub fn to_alternatescreen(&mut self) {
-        write!(self.stdout, "{}", screen::ToAlternateScreen).unwrap();
+fn push_cell(out: &mut String, fg: &mut Option<Ink>, inks: &[String; INKS], cell: Cell) {
+    // the tail of a wide glyph is already on screen with its head
+    if cell.c == WIDE_TAIL {
+        return;
+    }
+    if *fg != Some(cell.ink) {
+        out.push_str(&inks[cell.ink as usize]);
+        *fg = Some(cell.ink);
     }
-    */
+    out.push(cell.c);
 }
 
-const COLOR_WALL: &'static dyn color::Color = &color::Blue;
-const COLOR_TEXT: &'static dyn color::Color = &color::White;
-const COLOR_PLAYER: &'static dyn color::Color = &color::Green;
-const COLOR_HEAP: &'static dyn color::Color = &color::Yellow;
-const COLOR_ROBOT1: &'static dyn color::Color = &color::LightWhite;
-const COLOR_ROBOT2: &'static dyn color::Color = &color::LightWhite;
+const MINIMAP_WIDTH: usize = 16;
+const MINIMAP_HEIGHT: usize = 6;
+
+// The columns of the BSD sidebar, right of the arena
+const SIDEBAR_WIDTH: usize = 18;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOT: char = '🤖';
+
+    fn narrow(c: char) -> Cell {
+        Cell {
+            c,
+            ink: Ink::Text,
+            wide: false,
+        }
+    }
+
+    fn put_wide(screen: &mut Screen, x: usize, c: char) {
+        screen.put(
+            x,
+            0,
+            Cell {
+                c,
+                ink: Ink::Robot1,
+                wide: true,
+            },
+        );
+        screen.put(
+            x + 1,
+            0,
+            Cell {
+                c: WIDE_TAIL,
+                ink: Ink::Robot1,
+                wide: false,
+            },
+        );
+    }
+
+    // What is written to the terminal, without colors
+    fn present(screen: &mut Screen) -> String {
+        let mut terminal = Vec::new();
+        screen.present(&mut terminal, &Default::default(), "");
+        String::from_utf8(terminal).unwrap()
+    }
+
+    fn written(moves_and_cells: &[(u16, u16, &str)]) -> String {
+        let mut out = String::new();
+        for &(x, y, cells) in moves_and_cells {
+            out += &format!("{}{}", cursor::Goto(x, y), cells);
+        }
+        out + style::Reset.as_ref()
+    }
+
+    #[test]
+    fn present_changes() {
+        let mut screen = Screen::new(10, 2, 3, 1);
+
+        assert_eq!(
+            present(&mut screen),
+            written(&[(4, 2, "          "), (4, 3, "          ")])
+        );
+        assert_eq!(present(&mut screen), "");
+
+        // a short jump writes the cells again, a long one moves the cursor
+        screen.put(0, 0, narrow('a'));
+        screen.put(3, 0, narrow('b'));
+        screen.put(9, 0, narrow('c'));
+        assert_eq!(
+            present(&mut screen),
+            written(&[(4, 2, "a  b"), (13, 2, "c")])
+        );
+    }
+
+    #[test]
+    fn present_wide_glyphs() {
+        let mut screen = Screen::new(4, 1, 0, 0);
+        present(&mut screen);
+
+        put_wide(&mut screen, 0, ROBOT);
+        assert_eq!(present(&mut screen), written(&[(1, 1, "🤖")]));
+
+        // a wide glyph replaced by narrow ones
+        screen.put(0, 0, narrow('a'));
+        screen.put(1, 0, narrow('b'));
+        assert_eq!(present(&mut screen), written(&[(1, 1, "ab")]));
+
+        // narrow glyphs replaced by a wide one
+        put_wide(&mut screen, 1, ROBOT);
+        assert_eq!(present(&mut screen), written(&[(2, 1, "🤖")]));
+
+        // half of it does not stay alone
+        screen.put(2, 0, narrow('c'));
+        assert_eq!(present(&mut screen), written(&[(2, 1, " c")]));
+        assert_eq!(present(&mut screen), "");
+    }
+
+    // An arena of 100x50 cells seen through 40x20
+    fn viewport() -> Viewport {
+        let mut view = Viewport::new(100, 50);
+        view.resize(40, 20);
+        view
+    }
+
+    #[test]
+    fn follow_the_player() {
+        let mut view = viewport();
+
+        view.follow((0, 0));
+        assert_eq!((view.x, view.y), (0, 0));
+
+        // the player stays away from the borders of the view
+        view.follow((50, 25));
+        assert_eq!((view.x, view.y), (15, 10));
+        view.follow((19, 14));
+        assert_eq!((view.x, view.y), (15, 10));
+        view.follow((18, 26));
+        assert_eq!((view.x, view.y), (14, 11));
+
+        // but not beyond the borders of the arena
+        view.follow((99, 49));
+        assert_eq!((view.x, view.y), (60, 30));
+        assert!(view.contains((99, 49)));
+        assert!(!view.looking);
+    }
+
+    #[test]
+    fn pan_the_view() {
+        let mut view = viewport();
+        view.follow((0, 0));
+
+        assert!(view.pan(-1, -1));
+        assert_eq!((view.x, view.y), (0, 0));
+        for _ in 0..10 {
+            view.pan(1, 1);
+        }
+        assert_eq!((view.x, view.y), (60, 30));
+        assert!(view.looking);
+
+        // nothing to look at
+        let mut view = Viewport::new(40, 20);
+        view.resize(40, 20);
+        assert!(!view.pan(1, 1));
+        assert_eq!((view.x, view.y), (0, 0));
+    }
+
+    #[test]
+    fn back_to_the_player() {
+        let mut view = viewport();
+        view.follow((20, 10));
+        assert_eq!((view.x, view.y), (0, 0));
+
+        // the view stays while the player moves in it
+        view.pan(1, 0);
+        view.follow((21, 10));
+        assert_eq!((view.x, view.y), (10, 0));
+
+        // or does not move (e.g. a resize)
+        for _ in 0..5 {
+            view.pan(1, 0);
+        }
+        view.resize(40, 20);
+        assert_eq!((view.x, view.y), (60, 0));
+
+        // and follows again when the player moves out of it
+        view.follow((22, 10));
+        assert!(!view.looking);
+        assert_eq!((view.x, view.y), (18, 0));
+    }
+}
@@ -0,0 +1,187 @@
+use std::io::{BufRead, Write};
+
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::engine::Engine;
+use crate::enums::*;
+use crate::options::Options;
+
+/*
+ * A machine readable protocol for bots, one JSON object per line.
+ *
+ * Every turn the game prints:
+ *   {"type":"turn","state":"Playing","level":1,"score":0,"safe_teleports":1,
+ *    "seed":42,"accepted":true,"player":{"x":39,"y":10},"width":78,"height":21,
+ *    "arena":["   +  ", ...]}
+ * where in the arena ' ' is empty, '@' the player, '+' a Robot1, '#' a Robot2
+ * and '*' a heap, then it reads a command (a PlayerCommand):
+ *   {"command":"NW"}
+ * At the end it prints:
+ *   {"type":"end","state":"Dead","level":3,"score":420,"safe_teleports":0,"seed":42}
+ */
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Message<'a> {
+    Turn {
+        state: GameState,
+        level: u16,
+        score: u64,
+        safe_teleports: u16,
+        seed: u64,
+        // false if the last command has been refused
+        accepted: bool,
+        player: Position,
+        width: usize,
+        height: usize,
+        arena: Vec<String>,
+    },
+    End {
+        state: GameState,
+        level: u16,
+        score: u64,
+        safe_teleports: u16,
+        seed: u64,
+    },
+    Error {
+        message: &'a str,
+    },
+}
+
+#[derive(Serialize)]
+struct Position {
+    x: usize,
+    y: usize,
+}
+
+#[derive(Deserialize)]
+struct Request {
+    command: PlayerCommand,
+}
+
+pub fn run<R: BufRead, W: Write>(options: &Options, input: R, mut output: W) {
+    let seed = options
+        .seed
+        .unwrap_or_else(|| thread_rng().gen::<u32>() as u64);
+
+    let mut engine = Engine::new(options);
+    engine.new_game(seed);
+
+    let mut lines = input.lines();
+    let mut accepted = true;
+
+    while engine.state() == GameState::Playing {
+        send(&mut output, &turn(&engine, accepted));
+
+        let request = loop {
+            let line = match lines.next() {
+                Some(Ok(line)) => line,
+                // no more commands, the game ends here
+                _ => {
+                    send(&mut output, &end(&engine));
+                    return;
+                }
+            };
+
+            match serde_json::from_str::<Request>(&line) {
+                Ok(request) => break request,
+                Err(e) => send(
+                    &mut output,
+                    &Message::Error {
+                        message: &e.to_string(),
+                    },
+                ),
+            }
+        };
+
+        accepted = engine.player_command(request.command).accepted;
+
+        if engine.state() == GameState::Waiting {
+            engine.finish_waiting();
+        }
+        if engine.state() == GameState::LevelComplete {
+            engine.next_level();
+        }
+    }
+
+    send(&mut output, &end(&engine));
+}
+
+fn turn(engine: &Engine, accepted: bool) -> Message<'static> {
+    let (width, height) = engine.arena_size();
+    let (x, y) = engine.player_position();
+
+    let arena = engine
+        .arena()
+        .chunks(width)
+        .map(|row| {
+            row.iter()
+                .map(|object| match object {
+                    ObjectType::None => ' ',
+                    ObjectType::Player => '@',
+                    ObjectType::Heap => '*',
+                    ObjectType::Robot1 => '+',
+                    ObjectType::Robot2 => '#',
+                })
+                .collect()
+        })
+        .collect();
+
+    Message::Turn {
+        state: engine.state(),
+        level: engine.level() + 1,
+        score: engine.score(),
+        safe_teleports: engine.safeteleports(),
+        seed: engine.seed(),
+        accepted,
+        player: Position { x, y },
+        width,
+        height,
+        arena,
+    }
+}
+
+fn end(engine: &Engine) -> Message<'static> {
+    Message::End {
+        state: engine.state(),
+        level: engine.level() + 1,
+        score: engine.score(),
+        safe_teleports: engine.safeteleports(),
+        seed: engine.seed(),
+    }
+}
+
+fn send<W: Write>(output: &mut W, message: &Message) {
+    writeln!(output, "{}", serde_json::to_string(message).unwrap()).unwrap();
+    output.flush().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protocol() {
+        let mut options = Options::default();
+        options.seed = Some(1);
+
+        let input = "{\"command\":\"N\"}\nnonsense\n{\"command\":\"Wait\"}\n";
+        let mut output = Vec::new();
+        run(&options, input.as_bytes(), &mut output);
+
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(lines[0]["type"], "turn");
+        assert_eq!(lines[0]["state"], "Playing");
+        assert_eq!(lines[0]["seed"], 1);
+        assert_eq!(lines[0]["arena"].as_array().unwrap().len(), 21);
+        assert_eq!(lines[1]["type"], "turn");
+        assert_eq!(lines[2]["type"], "error");
+        assert_eq!(lines.last().unwrap()["type"], "end");
+    }
+}
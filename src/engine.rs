@@ -0,0 +1,875 @@
+use std::cmp::min;
+
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::enums::*;
+use crate::options::Options;
+
+const WAITING_KILLS: [u16; 2] = [1, 2];
+
+/*
+ * The game rules, without any knowledge of the terminal.
+ * A front-end feeds PlayerCommands and draws the resulting arena.
+ */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Engine {
+    safe_moves: bool,
+
+    // every random decision comes from here, so a seed identifies a game
+    seed: u64,
+    #[serde(with = "rng_state")]
+    rng: ChaCha8Rng,
+
+    arena_width: usize,
+    arena_height: usize,
+    arena: Vec<ObjectType>,
+    // the area of a normal arena, see scaled()
+    reference_area: usize,
+
+    state: GameState,
+    level: u16,
+    score: u64,
+    safeteleports: u16,
+    waiting_kills: u16,
+
+    player_x: usize,
+    player_y: usize,
+
+    initial_type1: u16,
+    initial_type2: u16,
+    increment_type1: u16,
+    increment_type2: u16,
+    maximum_type1: u16,
+    maximum_type2: u16,
+    score_type: [[u64; 2]; 2],
+    score_type_splatted: [u64; 2],
+    num_robots_per_safe: u16,
+    safe_score_boundary: u64,
+    initial_safe_teleports: u16,
+    free_safe_teleports: u16,
+    max_safe_teleports: u16,
+    moveable_heaps: bool,
+}
+
+/*
+ * What happened after a command (or a waiting step) has been applied
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Outcome {
+    // false if the command has been refused (e.g. a wall or a safe move)
+    pub accepted: bool,
+    pub state: GameState,
+    // points gained by the command
+    pub points: u64,
+}
+
+impl Engine {
+    pub fn new(options: &Options) -> Self {
+        let (arena_width, arena_height) = options.arena_size();
+        let arena = vec![ObjectType::None; arena_width * arena_height];
+        let (reference_width, reference_height) = options.reference_arena_size();
+
+        let score_type = [
+            [options.score_type1, options.score_type2],
+            [options.score_type1_waiting, options.score_type2_waiting],
+        ];
+        let score_type_splatted = [options.score_type1_splatted, options.score_type2_splatted];
+
+        Engine {
+            safe_moves: options.safe_moves,
+
+            seed: 0,
+            rng: ChaCha8Rng::seed_from_u64(0),
+
+            arena_width,
+            arena_height,
+            arena,
+            reference_area: reference_width * reference_height,
+
+            state: GameState::Playing,
+            level: 0,
+            score: 0,
+            safeteleports: 0,
+            waiting_kills: 0,
+
+            player_x: 0,
+            player_y: 0,
+
+            initial_type1: options.initial_type1,
+            initial_type2: options.initial_type2,
+            increment_type1: options.increment_type1,
+            increment_type2: options.increment_type2,
+            maximum_type1: options.maximum_type1,
+            maximum_type2: options.maximum_type2,
+            score_type,
+            score_type_splatted,
+            num_robots_per_safe: options.num_robots_per_safe,
+            safe_score_boundary: options.safe_score_boundary,
+            initial_safe_teleports: options.initial_safe_teleports,
+            free_safe_teleports: options.free_safe_teleports,
+            max_safe_teleports: options.max_safe_teleports,
+            moveable_heaps: options.moveable_heaps,
+        }
+    }
+
+    // The problems of an engine that has been stored (e.g. a saved game edited by hand)
+    pub fn validate(&self, options: &Options) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if (self.arena_width, self.arena_height) != options.arena_size() {
+            errors.push(format!(
+                "the arena ({}x{}) is not the one of the options ({}x{})",
+                self.arena_width,
+                self.arena_height,
+                options.arena_size().0,
+                options.arena_size().1
+            ));
+        }
+        if self.arena.len() != self.arena_width * self.arena_height {
+            errors.push(format!(
+                "the arena has {} cells instead of {}x{}",
+                self.arena.len(),
+                self.arena_width,
+                self.arena_height
+            ));
+        } else if self.state == GameState::Playing
+            && (self.player_x >= self.arena_width
+                || self.player_y >= self.arena_height
+                || self.arena[self.player_x + self.player_y * self.arena_width]
+                    != ObjectType::Player)
+        {
+            errors.push(format!(
+                "the player is not at {},{}",
+                self.player_x, self.player_y
+            ));
+        }
+
+        errors
+    }
+
+    pub fn arena(&self) -> &[ObjectType] {
+        &self.arena
+    }
+
+    pub fn arena_size(&self) -> (usize, usize) {
+        (self.arena_width, self.arena_height)
+    }
+
+    pub fn player_position(&self) -> (usize, usize) {
+        (self.player_x, self.player_y)
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn state(&self) -> GameState {
+        self.state
+    }
+
+    pub fn level(&self) -> u16 {
+        self.level
+    }
+
+    pub fn score(&self) -> u64 {
+        self.score
+    }
+
+    pub fn safeteleports(&self) -> u16 {
+        self.safeteleports
+    }
+
+    pub fn new_game(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+
+        self.score = 0;
+        self.level = 0;
+
+        self.generate_level();
+
+        self.safeteleports = self.initial_safe_teleports;
+    }
+
+    pub fn next_level(&mut self) {
+        self.level += 1;
+        self.generate_level();
+    }
+
+    fn generate_level(&mut self) {
+        self.clear_arena();
+
+        // any rules are accepted, the counts stop at the maximum instead of overflowing
+        let num_robots1 = self.scaled(min(
+            self.initial_type1
+                .saturating_add(self.increment_type1.saturating_mul(self.level)),
+            self.maximum_type1,
+        ));
+        let num_robots2 = self.scaled(min(
+            self.initial_type2
+                .saturating_add(self.increment_type2.saturating_mul(self.level)),
+            self.maximum_type2,
+        ));
+
+        let max_robots = self.arena_width * self.arena_height / 2;
+
+        if (num_robots1 + num_robots2) > max_robots {
+            self.state = GameState::GameComplete;
+        } else {
+            self.state = GameState::Playing;
+        }
+
+        self.safeteleports = self.safeteleports.saturating_add(self.free_safe_teleports);
+
+        if self.safeteleports > self.max_safe_teleports {
+            self.safeteleports = self.max_safe_teleports;
+        }
+
+        for _ in 0..num_robots1 {
+            self.place_randomly(ObjectType::Robot1);
+        }
+
+        for _ in 0..num_robots2 {
+            self.place_randomly(ObjectType::Robot2);
+        }
+    }
+
+    // The rules count the robots for a normal arena, a larger one gets more
+    fn scaled(&self, robots: u16) -> usize {
+        let area = self.arena_width * self.arena_height;
+
+        (robots as usize * area + self.reference_area / 2) / self.reference_area
+    }
+
+    fn clear_arena(&mut self) {
+        self.arena = vec![ObjectType::None; self.arena_width * self.arena_height];
+
+        self.player_x = self.arena_width / 2;
+        self.player_y = self.arena_height / 2;
+
+        self.arena[self.player_x + self.player_y * self.arena_width] = ObjectType::Player;
+    }
+
+    pub fn player_command(&mut self, command: PlayerCommand) -> Outcome {
+        if self.state != GameState::Playing {
+            return self.outcome(false, self.score);
+        }
+
+        let prev_score = self.score;
+
+        let accepted = match command {
+            PlayerCommand::NW
+            | PlayerCommand::N
+            | PlayerCommand::NE
+            | PlayerCommand::W
+            | PlayerCommand::Stay
+            | PlayerCommand::E
+            | PlayerCommand::SW
+            | PlayerCommand::S
+            | PlayerCommand::SE => {
+                let (dx, dy) = command.to_direction();
+                self.player_move(dx, dy, self.safe_moves)
+            }
+            PlayerCommand::SafeTeleport => self.safe_teleport(),
+            PlayerCommand::RandomTeleport => self.random_teleport(),
+            PlayerCommand::Wait => {
+                self.state = GameState::Waiting;
+                self.waiting_kills = 0;
+                true
+            }
+        };
+
+        self.outcome(accepted, prev_score)
+    }
+
+    /*
+     * Move the robots of one step while the player is waiting for the end.
+     * Call it until the state is no more GameState::Waiting.
+     */
+    pub fn wait_step(&mut self) -> Outcome {
+        if self.state != GameState::Waiting {
+            return self.outcome(false, self.score);
+        }
+
+        let prev_score = self.score;
+
+        let mut kills = self.waiting_kills;
+        self.player_move_k(0, 0, false, &mut kills);
+
+        if self.num_robots_per_safe > 0 {
+            while kills >= self.num_robots_per_safe {
+                self.safeteleports = self.safeteleports.saturating_add(1);
+                self.safeteleports = min(self.safeteleports, self.max_safe_teleports);
+                kills -= self.num_robots_per_safe;
+            }
+        }
+        self.waiting_kills = kills;
+
+        let mut score_step = self.score - prev_score;
+        if self.safe_score_boundary > 0 {
+            while score_step >= self.safe_score_boundary {
+                self.safeteleports = self.safeteleports.saturating_add(1);
+                self.safeteleports = min(self.safeteleports, self.max_safe_teleports);
+                score_step -= self.safe_score_boundary;
+            }
+        }
+
+        self.outcome(true, prev_score)
+    }
+
+    /*
+     * Run all the waiting steps at once (no animation)
+     */
+    pub fn finish_waiting(&mut self) -> Outcome {
+        let prev_score = self.score;
+
+        while self.state == GameState::Waiting {
+            self.wait_step();
+        }
+
+        self.outcome(true, prev_score)
+    }
+
+    fn outcome(&self, accepted: bool, prev_score: u64) -> Outcome {
+        Outcome {
+            accepted,
+            state: self.state,
+            points: self.score - prev_score,
+        }
+    }
+
+    fn player_move(&mut self, dx: i16, dy: i16, safe: bool) -> bool {
+        let mut kills = 0;
+        self.player_move_k(dx, dy, safe, &mut kills)
+    }
+
+    /*
+     * Parameter kills is used only in WAITING state
+     */
+    fn player_move_k(&mut self, dx: i16, dy: i16, safe: bool, kills: &mut u16) -> bool {
+        assert!(self.arena[self.player_x + self.player_y * self.arena_width] == ObjectType::Player);
+
+        // check boundaries
+        if (self.player_x == 0 && dx < 0)
+            || (self.player_y == 0 && dy < 0)
+            || (self.player_x == self.arena_width - 1 && dx > 0)
+            || (self.player_y == self.arena_height - 1 && dy > 0)
+        {
+            return false;
+        }
+
+        // create a new arena with only heap
+        let mut new_arena: Vec<ObjectType> = self
+            .arena
+            .iter()
+            .map(|&object| {
+                if object == ObjectType::Heap {
+                    ObjectType::Heap
+                } else {
+                    ObjectType::None
+                }
+            })
+            .collect();
+
+        // calculate new coords
+        let player_x = (self.player_x as i16 + dx) as usize;
+        let player_y = (self.player_y as i16 + dy) as usize;
+
+        // check for heap and eventually move it
+        if new_arena[player_x + player_y * self.arena_width] == ObjectType::Heap {
+            if self.moveable_heaps {
+                if !self.try_push_heap(&mut new_arena, player_x, player_y, dx, dy) {
+                    return false;
+                }
+            } else {
+                return false;
+            }
+        }
+
+        // move player
+        new_arena[player_x + player_y * self.arena_width] = ObjectType::Player;
+
+        // move robots
+        let result = self.move_robots(&mut new_arena, player_x, player_y);
+        *kills += result.kills;
+
+        // if 'safe moves' don't allow to dead
+        if result.dead && safe {
+            return false;
+        }
+
+        self.update_status(player_x, player_y, new_arena, &result);
+
+        if result.dead {
+            self.state = GameState::Dead;
+        } else {
+            let count = self.count_robots();
+            if count == 0 {
+                self.state = GameState::LevelComplete;
+            }
+        }
+
+        true
+    }
+
+    fn try_push_heap(
+        &self,
+        new_arena: &mut Vec<ObjectType>,
+        x: usize,
+        y: usize,
+        dx: i16,
+        dy: i16,
+    ) -> bool {
+        assert!(new_arena[x + y * self.arena_width] == ObjectType::Heap);
+
+        // check boundaries
+        if (x == 0 && dx < 0)
+            || (y == 0 && dy < 0)
+            || (x == self.arena_width - 1 && dx > 0)
+            || (y == self.arena_height - 1 && dy > 0)
+        {
+            return false;
+        }
+
+        // calculate new coords
+        let new_x = (x as i16 + dx) as usize;
+        let new_y = (y as i16 + dy) as usize;
+
+        if new_arena[new_x + new_y * self.arena_width] == ObjectType::None {
+            new_arena[x + y * self.arena_width] = ObjectType::None;
+            new_arena[new_x + new_y * self.arena_width] = ObjectType::Heap;
+            return true;
+        } else if new_arena[new_x + new_y * self.arena_width] == ObjectType::Heap {
+            if self.try_push_heap(new_arena, new_x, new_y, dx, dy) {
+                new_arena[x + y * self.arena_width] = ObjectType::None;
+                new_arena[new_x + new_y * self.arena_width] = ObjectType::Heap;
+                return true;
+            } else {
+                return false;
+            }
+        }
+
+        false
+    }
+
+    fn move_robots(
+        &self,
+        new_arena: &mut [ObjectType],
+        player_x: usize,
+        player_y: usize,
+    ) -> MoveRobotsResult {
+        let mut result = MoveRobotsResult::new();
+        let player = (player_x, player_y);
+
+        // move all robots of one step
+        for y in 0..self.arena_height {
+            for x in 0..self.arena_width {
+                let i = x + y * self.arena_width;
+                if self.arena[i].is_robot() {
+                    let robot = self.arena[i];
+                    self.move_robot(robot, (x, y), new_arena, player, &mut result);
+                }
+            }
+        }
+
+        // move Robots2 of a second step
+        let arena2 = new_arena.to_vec();
+        for object in new_arena.iter_mut() {
+            if *object == ObjectType::Robot2 {
+                *object = ObjectType::None;
+            }
+        }
+        for y in 0..self.arena_height {
+            for x in 0..self.arena_width {
+                let i = x + y * self.arena_width;
+                if arena2[i] == ObjectType::Robot2 {
+                    let robot = arena2[i];
+                    self.move_robot(robot, (x, y), new_arena, player, &mut result);
+                }
+            }
+        }
+
+        result
+    }
+
+    fn move_robot(
+        &self,
+        robot: ObjectType,
+        (x, y): (usize, usize),
+        new_arena: &mut [ObjectType],
+        (player_x, player_y): (usize, usize),
+        result: &mut MoveRobotsResult,
+    ) {
+        assert!(robot.is_robot());
+
+        let i = x + y * self.arena_width;
+
+        // the player jump over the robot?
+        if new_arena[i] == ObjectType::Player {
+            result.dead = true;
+            return;
+        }
+
+        // the player push heap over the robot?
+        if new_arena[i] == ObjectType::Heap {
+            result.score += self.score_type_splatted[robot as usize];
+            return;
+        }
+
+        // new robot coords
+        let new_x = match player_x as isize - x as isize {
+            d if d < 0 => x - 1,
+            d if d > 0 => x + 1,
+            _ => x,
+        };
+        let new_y = match player_y as isize - y as isize {
+            d if d < 0 => y - 1,
+            d if d > 0 => y + 1,
+            _ => y,
+        };
+        let new_i = new_x + new_y * self.arena_width;
+
+        // the robot jump over the player?
+        if new_arena[new_i] == ObjectType::Player {
+            result.dead = true;
+            return;
+        }
+
+        // the robot jump over a heap?
+        if new_arena[new_i] == ObjectType::Heap {
+            result.score += self.score_type[self.state as usize][robot as usize];
+            result.kills += WAITING_KILLS[robot as usize];
+            return;
+        }
+
+        // robot collision?
+        if new_arena[new_i].is_robot() {
+            let robot2 = new_arena[new_i];
+            result.score += self.score_type[self.state as usize][robot as usize];
+            result.kills += WAITING_KILLS[robot as usize];
+            result.score += self.score_type[self.state as usize][robot2 as usize];
+            result.kills += WAITING_KILLS[robot2 as usize];
+            new_arena[new_i] = ObjectType::Heap;
+            return;
+        }
+
+        // move the robot
+        new_arena[new_i] = robot;
+    }
+
+    fn count_robots(&self) -> u16 {
+        let mut count = 0;
+        for object in &self.arena {
+            if object.is_robot() {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    fn safe_teleport(&mut self) -> bool {
+        if self.safeteleports > 0 {
+            let moved = self.teleport(true);
+            if moved {
+                self.safeteleports -= 1;
+            }
+            moved
+        } else {
+            false
+        }
+    }
+
+    fn random_teleport(&mut self) -> bool {
+        let moved = self.teleport(false);
+
+        // this should never happen
+        if !moved {
+            panic!("No free space for a random teleport found");
+        }
+
+        moved
+    }
+
+    fn teleport(&mut self, safe: bool) -> bool {
+        let mut x_list: Vec<usize> = (0..self.arena_width).collect();
+        let mut y_list: Vec<usize> = (0..self.arena_height).collect();
+
+        x_list.shuffle(&mut self.rng);
+        y_list.shuffle(&mut self.rng);
+
+        for &y in y_list.iter() {
+            for &x in x_list.iter() {
+                let i = x + y * self.arena_width;
+
+                if self.arena[i] != ObjectType::None {
+                    continue;
+                }
+
+                let dx = x as i16 - self.player_x as i16;
+                let dy = y as i16 - self.player_y as i16;
+                let moved = self.player_move(dx, dy, safe);
+
+                if moved {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    fn place_randomly(&mut self, object: ObjectType) {
+        let mut x_list: Vec<usize> = (0..self.arena_width).collect();
+        let mut y_list: Vec<usize> = (0..self.arena_height).collect();
+
+        x_list.shuffle(&mut self.rng);
+        y_list.shuffle(&mut self.rng);
+
+        for &y in y_list.iter() {
+            for &x in x_list.iter() {
+                let i = x + y * self.arena_width;
+
+                if self.arena[i] == ObjectType::None {
+                    self.arena[i] = object;
+                    return;
+                }
+            }
+        }
+
+        panic!("Not able to place randomly");
+    }
+
+    fn update_status(
+        &mut self,
+        player_x: usize,
+        player_y: usize,
+        new_arena: Vec<ObjectType>,
+        result: &MoveRobotsResult,
+    ) {
+        self.player_x = player_x;
+        self.player_y = player_y;
+        self.arena = new_arena;
+        self.score += result.score;
+    }
+}
+
+/*
+ * ChaCha8Rng can't be serialized directly, but its whole state
+ * is the seed and the position in the stream
+ */
+mod rng_state {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct RngState {
+        seed: [u8; 32],
+        word_pos: u128,
+    }
+
+    pub fn serialize<S: Serializer>(rng: &ChaCha8Rng, serializer: S) -> Result<S::Ok, S::Error> {
+        RngState {
+            seed: rng.get_seed(),
+            word_pos: rng.get_word_pos(),
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ChaCha8Rng, D::Error> {
+        let state = RngState::deserialize(deserializer)?;
+
+        let mut rng = ChaCha8Rng::from_seed(state.seed);
+        rng.set_word_pos(state.word_pos);
+
+        Ok(rng)
+    }
+}
+
+struct MoveRobotsResult {
+    dead: bool,
+    score: u64,
+    kills: u16,
+}
+
+impl MoveRobotsResult {
+    pub fn new() -> Self {
+        MoveRobotsResult {
+            dead: false,
+            score: 0,
+            kills: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Engine {
+    // An engine with an empty arena and the player in the middle
+    pub fn empty(options: &Options) -> Self {
+        let mut engine = Engine::new(options);
+        engine.clear_arena();
+        engine.state = GameState::Playing;
+        engine
+    }
+
+    pub fn put(&mut self, x: usize, y: usize, object: ObjectType) {
+        self.arena[x + y * self.arena_width] = object;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::BoardSize;
+
+    fn engine() -> Engine {
+        Engine::empty(&Options::default())
+    }
+
+    #[test]
+    fn new_game() {
+        let mut engine = Engine::new(&Options::default());
+        engine.new_game(1);
+
+        assert_eq!(engine.state(), GameState::Playing);
+        assert_eq!(engine.level(), 0);
+        assert_eq!(engine.score(), 0);
+        assert_eq!(engine.count_robots(), 10);
+    }
+
+    #[test]
+    fn robots_scale_with_the_arena() {
+        let mut options = Options::default();
+        options.boardsize = BoardSize::Huge;
+        let mut engine = Engine::new(&options);
+        engine.new_game(1);
+
+        // 158x45 is 4.34 times 78x21: 35 + 9 robots instead of 8 + 2
+        assert_eq!(engine.count_robots(), 44);
+    }
+
+    #[test]
+    fn large_increments() {
+        let mut options = Options::default();
+        options.increment_type1 = 40000;
+        options.maximum_type1 = 30;
+        options.initial_type2 = 0;
+        options.increment_type2 = 0;
+        options.free_safe_teleports = u16::MAX;
+        let mut engine = Engine::new(&options);
+        engine.new_game(1);
+        engine.next_level();
+        engine.next_level();
+
+        assert_eq!(engine.count_robots(), 30);
+    }
+
+    #[test]
+    fn same_seed_same_game() {
+        let mut engine1 = Engine::new(&Options::default());
+        let mut engine2 = Engine::new(&Options::default());
+        engine1.new_game(42);
+        engine2.new_game(42);
+
+        assert!(engine1.arena() == engine2.arena());
+
+        engine1.player_command(PlayerCommand::RandomTeleport);
+        engine2.player_command(PlayerCommand::RandomTeleport);
+
+        assert!(engine1.arena() == engine2.arena());
+        assert_eq!(engine1.player_position(), engine2.player_position());
+    }
+
+    #[test]
+    fn save_and_resume() {
+        let mut engine = Engine::new(&Options::default());
+        engine.new_game(3);
+        engine.player_command(PlayerCommand::RandomTeleport);
+
+        let serialized = serde_json::to_string(&engine).unwrap();
+        let mut resumed: Engine = serde_json::from_str(&serialized).unwrap();
+
+        assert!(resumed.arena() == engine.arena());
+
+        engine.player_command(PlayerCommand::RandomTeleport);
+        resumed.player_command(PlayerCommand::RandomTeleport);
+
+        assert!(resumed.arena() == engine.arena());
+        assert_eq!(resumed.player_position(), engine.player_position());
+    }
+
+    #[test]
+    fn broken_saved_engine() {
+        let options = Options::default();
+        let mut engine = Engine::new(&options);
+        engine.new_game(3);
+        assert!(engine.validate(&options).is_empty());
+
+        let mut larger = options.clone();
+        larger.boardsize = BoardSize::Large;
+        assert_eq!(
+            engine.validate(&larger),
+            ["the arena (78x21) is not the one of the options (118x33)"]
+        );
+
+        let mut truncated = engine.clone();
+        truncated.arena.pop();
+        assert_eq!(
+            truncated.validate(&options),
+            ["the arena has 1637 cells instead of 78x21"]
+        );
+
+        let mut lost = engine.clone();
+        lost.player_x = 100;
+        assert_eq!(lost.validate(&options).len(), 1);
+    }
+
+    #[test]
+    fn robots_collide() {
+        let mut engine = engine();
+        let (px, py) = engine.player_position();
+        engine.put(px - 2, py - 1, ObjectType::Robot1);
+        engine.put(px - 2, py + 1, ObjectType::Robot1);
+
+        let outcome = engine.player_command(PlayerCommand::Stay);
+
+        assert!(outcome.accepted);
+        assert_eq!(outcome.state, GameState::LevelComplete);
+        assert_eq!(outcome.points, 20);
+        assert_eq!(
+            engine.arena()[px - 1 + py * engine.arena_width],
+            ObjectType::Heap
+        );
+    }
+
+    #[test]
+    fn safe_moves() {
+        let mut engine = engine();
+        let (px, py) = engine.player_position();
+        engine.put(px + 2, py, ObjectType::Robot1);
+
+        let outcome = engine.player_command(PlayerCommand::E);
+
+        assert!(!outcome.accepted);
+        assert_eq!(outcome.state, GameState::Playing);
+        assert_eq!(engine.player_position(), (px, py));
+    }
+
+    #[test]
+    fn waiting() {
+        let mut engine = engine();
+        let (px, py) = engine.player_position();
+        engine.put(px + 3, py, ObjectType::Robot1);
+        engine.put(px + 2, py, ObjectType::Heap);
+
+        engine.player_command(PlayerCommand::Wait);
+        let outcome = engine.wait_step();
+
+        assert_eq!(outcome.state, GameState::LevelComplete);
+        assert_eq!(outcome.points, 10);
+        assert!(!engine.wait_step().accepted);
+    }
+}
@@ -1,4 +1,6 @@
-#[derive(PartialEq, Eq, Clone, Copy)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum ObjectType {
     Robot1 = 0,
     Robot2 = 1,
@@ -13,7 +15,7 @@ impl ObjectType {
     }
 }
 
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum GameState {
     Playing = 0,
     Waiting = 1,
@@ -22,6 +24,7 @@ pub enum GameState {
     Dead,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum PlayerCommand {
     NW,
     N,
@@ -38,7 +41,7 @@ pub enum PlayerCommand {
 }
 
 impl PlayerCommand {
-    pub fn to_direction(&self) -> (i16, i16) {
+    pub fn to_direction(self) -> (i16, i16) {
         match self {
             PlayerCommand::NW => (-1, -1),
             PlayerCommand::N => (0, -1),
@@ -1,232 +1,203 @@
-use std::cmp::min;
-use std::io::stdin;
-use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use termion::event::Key;
-use termion::input::TermRead;
+use rand::{thread_rng, Rng};
 
-use rand::seq::SliceRandom;
-use rand::thread_rng;
-
-use crate::board::Board;
+use crate::ai::{self, Strategy};
+use crate::engine::Engine;
 use crate::enums::*;
+use crate::input::{new_input, Action, AutoplayInput, Context, InputSource, Keyboard, ReplayInput};
 use crate::options::Options;
+use crate::recording::Recording;
+use crate::renderer::{new_renderer, Renderer};
+use crate::savegame::SavedGame;
+use crate::scores::HighScores;
 
 const CHANGE_DELAY: u64 = 1000;
 const WAITING_DELAY: u64 = 200;
-
-const WAITING_KILLS: [u16; 2] = [1, 2];
+const RUN_DELAY: u64 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GameMode {
+    Normal,
+    // moves can be undone, but scores don't count
+    Practice,
+    // a recorded game, nothing is saved
+    Replay,
+    // the computer plays, scores don't count
+    Autoplay,
+}
 
 pub struct Game {
-    board: Board,
-
-    safe_moves: bool,
-
-    arena_width: usize,
-    arena_height: usize,
-    arena: Vec<ObjectType>,
-
-    state: GameState,
-    level: u16,
-    score: u64,
-    safeteleports: u16,
-
-    player_x: usize,
-    player_y: usize,
-
-    initial_type1: u16,
-    initial_type2: u16,
-    increment_type1: u16,
-    increment_type2: u16,
-    maximum_type1: u16,
-    maximum_type2: u16,
-    score_type: [[u64; 2]; 2],
-    score_type_splatted: [u64; 2],
-    num_robots_per_safe: u16,
-    safe_score_boundary: u64,
-    initial_safe_teleports: u16,
-    free_safe_teleports: u16,
-    max_safe_teleports: u16,
-    moveable_heaps: bool,
+    board: Box<dyn Renderer>,
+    input: Box<dyn InputSource>,
+    // replays and autoplays read it too
+    keyboard: Keyboard,
+    engine: Engine,
+    options: Options,
+    mode: GameMode,
+    animations: bool,
+    // the terminal is too small for the board
+    paused: bool,
+
+    // seed requested on the command line, used only by the first game
+    seed: Option<u64>,
+
+    recording: Option<Recording>,
+
+    history: Vec<Engine>,
+    future: Vec<Engine>,
 }
 
 impl Game {
-    pub fn new(options: &Options) -> Result<Self, String> {
-        let mut board = Board::new(options);
+    pub fn new(options: &Options, keyboard: &Keyboard) -> Result<Self, String> {
+        let mut board = new_renderer(options)?;
 
         board.init()?;
 
-        let (arena_width, arena_height) = board.get_arena_size();
-        let arena = vec![ObjectType::None; arena_width * arena_height];
-
-        let score_type = [
-            [options.score_type1, options.score_type2],
-            [options.score_type1_waiting, options.score_type2_waiting],
-        ];
-        let score_type_splatted = [options.score_type1_splatted, options.score_type2_splatted];
-
-        Ok(Game {
-            board,
+        let input = new_input(options, keyboard);
 
-            safe_moves: options.safe_moves,
-
-            arena_width,
-            arena_height,
-            arena,
-
-            state: GameState::Playing,
-            level: 0,
-            score: 0,
-            safeteleports: 0,
-
-            player_x: 0,
-            player_y: 0,
-
-            initial_type1: options.initial_type1,
-            initial_type2: options.initial_type2,
-            increment_type1: options.increment_type1,
-            increment_type2: options.increment_type2,
-            maximum_type1: options.maximum_type1,
-            maximum_type2: options.maximum_type2,
-            score_type,
-            score_type_splatted,
-            num_robots_per_safe: options.num_robots_per_safe,
-            safe_score_boundary: options.safe_score_boundary,
-            initial_safe_teleports: options.initial_safe_teleports,
-            free_safe_teleports: options.free_safe_teleports,
-            max_safe_teleports: options.max_safe_teleports,
-            moveable_heaps: options.moveable_heaps,
-        })
+        Ok(Game::with(board, input, keyboard, options))
     }
 
-    pub fn new_game(&mut self) {
-        self.score = 0;
-        self.level = 0;
-
-        self.generate_level();
-
-        self.safeteleports = self.initial_safe_teleports;
-
-        self.board.set_score(self.score);
-        self.board.set_level(self.level);
-        self.board.set_safeteleports(self.safeteleports);
-
-        self.board.update(&self.arena);
-    }
-
-    pub fn generate_level(&mut self) {
-        self.clear_arena();
-
-        let num_robots1 = min(
-            self.initial_type1 + self.increment_type1 * self.level,
-            self.maximum_type1,
-        );
-        let num_robots2 = min(
-            self.initial_type2 + self.increment_type2 * self.level,
-            self.maximum_type2,
-        );
-
-        let max_robots = (self.arena_width * self.arena_height / 2) as u16;
-
-        if (num_robots1 + num_robots2) > max_robots {
-            self.state = GameState::GameComplete;
+    fn with(
+        board: Box<dyn Renderer>,
+        input: Box<dyn InputSource>,
+        keyboard: &Keyboard,
+        options: &Options,
+    ) -> Self {
+        let mode = if options.practice {
+            GameMode::Practice
         } else {
-            self.state = GameState::Playing;
-        }
-
-        self.safeteleports += self.free_safe_teleports;
+            GameMode::Normal
+        };
 
-        if self.safeteleports > self.max_safe_teleports {
-            self.safeteleports = self.max_safe_teleports;
+        Game {
+            board,
+            input,
+            keyboard: keyboard.clone(),
+            engine: Engine::new(options),
+            options: options.clone(),
+            mode,
+            animations: true,
+            paused: false,
+            seed: options.seed,
+            recording: None,
+            history: Vec::new(),
+            future: Vec::new(),
         }
+    }
 
-        for _ in 0..num_robots1 {
-            self.place_randomly(ObjectType::Robot1);
-        }
+    pub fn new_game(&mut self) {
+        let seed = match self.seed.take() {
+            Some(seed) => seed,
+            None => thread_rng().gen::<u32>() as u64,
+        };
 
-        for _ in 0..num_robots2 {
-            self.place_randomly(ObjectType::Robot2);
+        self.engine.new_game(seed);
+        self.history.clear();
+        self.future.clear();
+        if self.mode == GameMode::Normal || self.mode == GameMode::Autoplay {
+            self.recording = Some(Recording::new(&self.options, seed));
         }
+        self.update_board();
+    }
 
-        /*
-        // Test
-        self.arena[self.player_x - 1 + self.player_y * self.arena_width] = ObjectType::Heap;
-        self.arena[self.player_x - 2 + self.player_y * self.arena_width] = ObjectType::Heap;
-
-        self.arena[self.player_x - 5 + self.player_y * self.arena_width] = ObjectType::Robot1;
+    pub fn run(&mut self) {
+        self.play();
+        self.board.close();
+    }
 
-        // self.arena[self.player_x - 1 + (self.player_y - 5) * self.arena_width] = ObjectType::Robot1;
-        // self.arena[self.player_x - 2 + (self.player_y - 5) * self.arena_width] = ObjectType::Robot1;
+    /*
+     * Play again a recorded game
+     */
+    pub fn replay(&mut self, recording: &Recording) {
+        self.mode = GameMode::Replay;
+        self.input = Box::new(ReplayInput::new(
+            &self.keyboard,
+            &self.options.keys,
+            &recording.commands,
+        ));
 
-        self.arena[self.player_x - 9 + self.player_y * self.arena_width] = ObjectType::Robot2;
-        // self.arena[self.player_x + 2 + self.player_y * self.arena_width] = ObjectType::Robot1;
-        */
+        self.engine.new_game(recording.seed);
+        self.update_board();
 
-        self.board.set_score(self.score);
-        self.board.set_level(self.level);
-        self.board.set_safeteleports(self.safeteleports);
-        self.board.update(&self.arena);
-    }
+        self.play();
 
-    pub fn clear_arena(&mut self) {
-        self.arena = vec![ObjectType::None; self.arena_width * self.arena_height];
+        self.board.show_message("End of replay");
+        self.next_action(Context::Dialog);
 
-        self.player_x = self.arena_width / 2;
-        self.player_y = self.arena_height / 2;
+        self.board.close();
+    }
 
-        self.arena[self.player_x + self.player_y * self.arena_width] = ObjectType::Player;
+    /*
+     * Let the computer play
+     */
+    pub fn autoplay(&mut self, strategy: Strategy, delay: u64) {
+        self.mode = GameMode::Autoplay;
+        self.input = Box::new(AutoplayInput::new(
+            &self.keyboard,
+            &self.options.keys,
+            strategy,
+            delay,
+        ));
+
+        self.new_game();
+        self.run();
     }
 
-    pub fn run(&mut self) {
-        for c in stdin().keys() {
-            if let Key::Char(c) = c.unwrap() {
-                match c.to_ascii_lowercase() {
-                    'q' => {
-                        if self.ask_quit() {
-                            break;
-                        } else {
-                            self.board.update(&self.arena);
+    fn play(&mut self) {
+        while let Some(action) = self.next_action(Context::Playing) {
+            match action {
+                Action::Command(command) => self.player_command(command),
+                Action::Run(direction) => self.run_towards(direction),
+                Action::Quit => {
+                    if self.mode == GameMode::Replay
+                        || self.mode == GameMode::Autoplay
+                        || self.ask_quit()
+                    {
+                        if self.mode == GameMode::Normal {
+                            self.save_game();
                         }
+                        break;
+                    } else {
+                        self.update_board();
                     }
-                    '7' | 'y' => self.player_command(PlayerCommand::NW),
-                    '8' | 'k' => self.player_command(PlayerCommand::N),
-                    '9' | 'u' => self.player_command(PlayerCommand::NE),
-                    '4' | 'h' => self.player_command(PlayerCommand::W),
-                    '5' | ' ' => self.player_command(PlayerCommand::Stay),
-                    '6' | 'l' => self.player_command(PlayerCommand::E),
-                    '1' | 'b' => self.player_command(PlayerCommand::SW),
-                    '2' | 'j' => self.player_command(PlayerCommand::S),
-                    '3' | 'n' => self.player_command(PlayerCommand::SE),
-                    '+' | '\n' => self.player_command(PlayerCommand::SafeTeleport),
-                    '-' | 't' => self.player_command(PlayerCommand::RandomTeleport),
-                    'w' => self.player_command(PlayerCommand::Wait),
-                    '?' => self.show_help(),
-                    // 's' => {self.board.to_mainscreen()}
-                    // 'a' => {self.board.to_alternatescreen()}
-                    _ => {}
                 }
+                Action::Help => self.show_help(),
+                Action::Describe => self.board.describe(),
+                Action::Pan(dx, dy) => self.board.pan(dx, dy),
+                Action::Undo if self.mode == GameMode::Practice => self.undo(),
+                Action::Redo if self.mode == GameMode::Practice => self.redo(),
+                _ => {}
             }
 
-            if self.state == GameState::Waiting {
+            if self.engine.state() == GameState::Waiting {
                 self.waiting();
                 // here I will be in state DEAD or LEVELCOMPLETE so I will match one of the following 'if'
             };
-            if self.state == GameState::Dead {
+            if self.engine.state() == GameState::Dead {
+                self.store_recording();
                 self.show_dead();
-                if self.ask_new_game() {
-                    self.new_game();
+                if self.mode == GameMode::Practice && self.ask_undo() {
+                    self.undo();
                 } else {
-                    break; // exit game
+                    self.record_score();
+                    if self.ask_new_game() {
+                        self.new_game();
+                    } else {
+                        break; // exit game
+                    }
                 }
             };
-            if self.state == GameState::LevelComplete {
+            if self.engine.state() == GameState::LevelComplete {
                 self.show_level_complete();
-                self.level += 1;
-                self.generate_level();
+                self.engine.next_level();
+                self.update_board();
                 // here I will be in state PLAYING or GAMECOMPLETE so I could match the following 'if'
             };
-            if self.state == GameState::GameComplete {
+            if self.engine.state() == GameState::GameComplete {
+                self.store_recording();
+                self.record_score();
                 if self.show_game_complete_and_ask() {
                     self.new_game();
                 } else {
@@ -234,451 +205,389 @@ impl Game {
                 }
             };
         }
-
-        self.board.close();
     }
 
-    fn player_command(&mut self, command: PlayerCommand) {
-        if self.state != GameState::Playing {
-            return;
-        }
-
-        match command {
-            PlayerCommand::NW
-            | PlayerCommand::N
-            | PlayerCommand::NE
-            | PlayerCommand::W
-            | PlayerCommand::Stay
-            | PlayerCommand::E
-            | PlayerCommand::SW
-            | PlayerCommand::S
-            | PlayerCommand::SE => {
-                let (dx, dy) = command.to_direction();
-                self.player_move(dx, dy, self.safe_moves);
-            }
-            PlayerCommand::SafeTeleport => self.safe_teleport(),
-            PlayerCommand::RandomTeleport => self.random_teleport(),
-            PlayerCommand::Wait => {
-                self.state = GameState::Waiting;
+    // The next action of the player, the game is paused while the terminal is too small
+    fn next_action(&mut self, context: Context) -> Option<Action> {
+        loop {
+            match self.input.next_action(context, &self.engine)? {
+                Action::Resize => self.paused = !self.board.resize(),
+                _ if self.paused => (),
+                action => return Some(action),
             }
         }
-        self.board.update(&self.arena);
-    }
-
-    fn player_move(&mut self, dx: i16, dy: i16, safe: bool) -> bool {
-        let mut kills = 0;
-        self.player_move_k(dx, dy, safe, &mut kills)
     }
 
-    /*
-     * Parameter kills is used only in WAITING state
-     */
-    fn player_move_k(&mut self, dx: i16, dy: i16, safe: bool, kills: &mut u16) -> bool {
-        assert!(self.arena[self.player_x + self.player_y * self.arena_width] == ObjectType::Player);
-
-        // check boundaries
-        if (self.player_x == 0 && dx < 0)
-            || (self.player_y == 0 && dy < 0)
-            || (self.player_x == self.arena_width - 1 && dx > 0)
-            || (self.player_y == self.arena_height - 1 && dy > 0)
-        {
-            return false;
+    fn player_command(&mut self, command: PlayerCommand) {
+        if let Some(recording) = self.recording.as_mut() {
+            recording.push(command);
         }
 
-        // create a new arena with only heap
-        let mut new_arena = vec![ObjectType::None; self.arena_width * self.arena_height];
-        for i in 0..self.arena_width * self.arena_height {
-            if self.arena[i] == ObjectType::Heap {
-                new_arena[i] = ObjectType::Heap;
-            }
-        }
+        let previous = if self.mode == GameMode::Practice {
+            Some(self.engine.clone())
+        } else {
+            None
+        };
 
-        // calculate new coords
-        let player_x = (self.player_x as i16 + dx) as usize;
-        let player_y = (self.player_y as i16 + dy) as usize;
+        let outcome = self.engine.player_command(command);
 
-        // check for heap and eventually move it
-        if new_arena[player_x + player_y * self.arena_width] == ObjectType::Heap {
-            if self.moveable_heaps {
-                if !self.try_push_heap(&mut new_arena, player_x, player_y, dx, dy) {
-                    return false;
-                }
-            } else {
-                return false;
+        if let Some(previous) = previous {
+            if outcome.accepted {
+                self.history.push(previous);
+                self.future.clear();
             }
         }
 
-        // move player
-        new_arena[player_x + player_y * self.arena_width] = ObjectType::Player;
-
-        // move robots
-        let result = self.move_robots(&mut new_arena, player_x, player_y);
-        *kills += result.kills;
-
-        // if 'safe moves' don't allow to dead
-        if result.dead && safe {
-            return false;
-        }
-
-        self.update_status(player_x, player_y, new_arena, &result);
+        self.update_board();
+    }
 
-        if result.dead {
-            self.state = GameState::Dead;
-        } else {
-            let count = self.count_robots();
-            if count == 0 {
-                self.state = GameState::LevelComplete;
+    // The moves of a run, the first one too must be safe
+    fn run_towards(&mut self, direction: PlayerCommand) {
+        while ai::safe_moves(&self.engine).contains(&direction) {
+            self.player_command(direction);
+            if ai::run_stops(&self.engine, direction) {
+                break;
             }
+            self.sleep(RUN_DELAY);
         }
+    }
 
-        true
-    }
-
-    fn try_push_heap(
-        &self,
-        new_arena: &mut Vec<ObjectType>,
-        x: usize,
-        y: usize,
-        dx: i16,
-        dy: i16,
-    ) -> bool {
-        assert!(new_arena[x + y * self.arena_width] == ObjectType::Heap);
-
-        // check boundaries
-        if (x == 0 && dx < 0)
-            || (y == 0 && dy < 0)
-            || (x == self.arena_width - 1 && dx > 0)
-            || (y == self.arena_height - 1 && dy > 0)
-        {
-            return false;
+    fn undo(&mut self) {
+        if let Some(engine) = self.history.pop() {
+            let current = std::mem::replace(&mut self.engine, engine);
+            self.future.push(current);
         }
+        self.update_board();
+    }
 
-        // calculate new coords
-        let new_x = (x as i16 + dx) as usize;
-        let new_y = (y as i16 + dy) as usize;
-
-        if new_arena[new_x + new_y * self.arena_width] == ObjectType::None {
-            new_arena[x + y * self.arena_width] = ObjectType::None;
-            new_arena[new_x + new_y * self.arena_width] = ObjectType::Heap;
-            return true;
-        } else if new_arena[new_x + new_y * self.arena_width] == ObjectType::Heap {
-            if self.try_push_heap(new_arena, new_x, new_y, dx, dy) {
-                new_arena[x + y * self.arena_width] = ObjectType::None;
-                new_arena[new_x + new_y * self.arena_width] = ObjectType::Heap;
-                return true;
-            } else {
-                return false;
-            }
+    fn redo(&mut self) {
+        if let Some(engine) = self.future.pop() {
+            let current = std::mem::replace(&mut self.engine, engine);
+            self.history.push(current);
         }
-
-        false
+        self.update_board();
     }
 
-    fn move_robots(
-        &self,
-        new_arena: &mut [ObjectType],
-        player_x: usize,
-        player_y: usize,
-    ) -> MoveRobotsResult {
-        let mut result = MoveRobotsResult::new();
+    pub fn resume(&mut self, saved: SavedGame) {
+        self.engine = saved.engine;
+        self.recording = saved.recording;
+        self.update_board();
+    }
 
-        // move all robots of one step
-        for y in 0..self.arena_height {
-            for x in 0..self.arena_width {
-                let i = x + y * self.arena_width;
-                if self.arena[i].is_robot() {
-                    let robot = self.arena[i];
-                    self.move_robot(robot, x, y, new_arena, player_x, player_y, &mut result);
-                }
-            }
-        }
+    fn save_game(&mut self) {
+        let saved = SavedGame {
+            options: self.options.clone(),
+            engine: self.engine.clone(),
+            recording: self.recording.take(),
+        };
+        saved.store();
+    }
 
-        // move Robots2 of a second step
-        let mut arena2 = vec![ObjectType::None; self.arena_width * self.arena_height];
-        for i in 0..self.arena_width * self.arena_height {
-            arena2[i] = new_arena[i];
-            if new_arena[i] == ObjectType::Robot2 {
-                new_arena[i] = ObjectType::None;
-            }
-        }
-        for y in 0..self.arena_height {
-            for x in 0..self.arena_width {
-                let i = x + y * self.arena_width;
-                if arena2[i] == ObjectType::Robot2 {
-                    let robot = arena2[i];
-                    self.move_robot(robot, x, y, new_arena, player_x, player_y, &mut result);
-                }
-            }
+    fn store_recording(&mut self) {
+        if let Some(recording) = self.recording.take() {
+            recording.store();
         }
-
-        result
     }
 
-    fn move_robot(
-        &self,
-        robot: ObjectType,
-        x: usize,
-        y: usize,
-        new_arena: &mut [ObjectType],
-        player_x: usize,
-        player_y: usize,
-        result: &mut MoveRobotsResult,
-    ) {
-        assert!(robot.is_robot());
+    fn waiting(&mut self) {
+        while self.engine.state() == GameState::Waiting {
+            self.sleep(WAITING_DELAY);
 
-        let i = x + y * self.arena_width;
+            self.engine.wait_step();
 
-        // the player jump over the robot?
-        if new_arena[i] == ObjectType::Player {
-            result.dead = true;
-            return;
+            self.update_board();
         }
+    }
 
-        // the player push heap over the robot?
-        if new_arena[i] == ObjectType::Heap {
-            result.score += self.score_type_splatted[robot as usize];
-            return;
+    fn update_board(&mut self) {
+        self.board.set_status(
+            self.engine.level(),
+            self.engine.score(),
+            self.engine.safeteleports(),
+            self.engine.seed(),
+        );
+        if self.board.wants_safe_moves() && self.engine.state() == GameState::Playing {
+            self.board.set_safe_moves(&ai::safe_moves(&self.engine));
         }
+        self.board.update(self.engine.arena());
+    }
 
-        // new robot coords
-        let new_x = match player_x as isize - x as isize {
-            d if d < 0 => x - 1,
-            d if d > 0 => x + 1,
-            _ => x,
-        };
-        let new_y = match player_y as isize - y as isize {
-            d if d < 0 => y - 1,
-            d if d > 0 => y + 1,
-            _ => y,
-        };
-        let new_i = new_x + new_y * self.arena_width;
-
-        // the robot jump over the player?
-        if new_arena[new_i] == ObjectType::Player {
-            result.dead = true;
+    /*
+     * Waits between the steps of an animation, a resized terminal is drawn
+     * again at once (and the animation stops while the terminal is too small)
+     */
+    fn sleep(&mut self, millis: u64) {
+        if !self.animations {
             return;
         }
 
-        // the robot jump over a heap?
-        if new_arena[new_i] == ObjectType::Heap {
-            result.score += self.score_type[self.state as usize][robot as usize];
-            result.kills += WAITING_KILLS[robot as usize];
-            return;
+        let deadline = Instant::now() + Duration::from_millis(millis);
+        loop {
+            let timeout = if self.paused {
+                Duration::from_millis(millis)
+            } else {
+                deadline.saturating_duration_since(Instant::now())
+            };
+            if self.input.resized_before(timeout) {
+                self.paused = !self.board.resize();
+            } else if !self.paused {
+                break;
+            }
         }
+    }
 
-        // robot collision?
-        if new_arena[new_i].is_robot() {
-            let robot2 = new_arena[new_i];
-            result.score += self.score_type[self.state as usize][robot as usize];
-            result.kills += WAITING_KILLS[robot as usize];
-            result.score += self.score_type[self.state as usize][robot2 as usize];
-            result.kills += WAITING_KILLS[robot2 as usize];
-            new_arena[new_i] = ObjectType::Heap;
+    fn record_score(&mut self) {
+        if self.mode != GameMode::Normal {
             return;
         }
 
-        // move the robot
-        new_arena[new_i] = robot;
-    }
+        let rules = self.options.profile();
 
-    fn count_robots(&self) -> u16 {
-        // for (i, <item>) in new_arena.iter_mut().enumerate().take(self.arena_width * self.arena_height) {
-        let mut count = 0;
-        for object in &self.arena {
-            if object.is_robot() {
-                count += 1;
+        let mut highscores = match HighScores::load() {
+            Ok(highscores) => highscores,
+            Err(e) => {
+                self.board.show_dialog(&format!(
+                    " The high scores cannot be read, this score is not recorded:\n\n {}\n",
+                    e
+                ));
+                self.next_action(Context::Dialog);
+                return;
             }
+        };
+        let board_size = self.options.board_size();
+        let position = highscores.add(
+            self.engine.score(),
+            self.engine.level() + 1,
+            &rules,
+            board_size,
+        );
+
+        if position.is_some() {
+            highscores.store();
+            self.board
+                .show_dialog(&highscores.format_table(&rules, board_size, position));
+            self.next_action(Context::Dialog);
         }
-        count
     }
 
-    fn safe_teleport(&mut self) {
-        if self.safeteleports > 0 {
-            let moved = self.teleport(true);
-            if moved {
-                self.safeteleports -= 1;
-                self.board.set_safeteleports(self.safeteleports);
-            }
-        }
+    fn show_dead(&mut self) {
+        let (player_x, player_y) = self.engine.player_position();
+        self.board
+            .show_player_message(player_x, player_y, "AARRrrgghhhh....");
+        self.sleep(CHANGE_DELAY);
     }
 
-    fn random_teleport(&mut self) {
-        let moved = self.teleport(false);
+    fn show_level_complete(&mut self) {
+        let (player_x, player_y) = self.engine.player_position();
+        self.board
+            .show_player_message(player_x, player_y, "Yahoo!!");
+        self.sleep(CHANGE_DELAY);
+    }
 
-        // this should never happen
-        if !moved {
-            panic!("No free space for a random teleport found");
-        }
+    fn ask_quit(&mut self) -> bool {
+        let message = if self.mode == GameMode::Practice {
+            "Do you really want to quit?\n(y\\n)"
+        } else {
+            "Do you really want to quit?\nThe game will be saved.\n(y\\n)"
+        };
+        self.confirm(message)
     }
 
-    fn teleport(&mut self, safe: bool) -> bool {
-        let mut rng = thread_rng();
+    fn ask_undo(&mut self) -> bool {
+        let message = "You are dead.\nDo you want to undo the last move?\n(y\\n)";
+        self.confirm(message)
+    }
 
-        let mut x_list: Vec<usize> = (0..self.arena_width).collect();
-        let mut y_list: Vec<usize> = (0..self.arena_height).collect();
+    pub fn ask_resume(&mut self) -> bool {
+        let message = "Do you want to resume the saved game?\n(y\\n)";
+        self.confirm(message)
+    }
 
-        x_list.shuffle(&mut rng);
-        y_list.shuffle(&mut rng);
+    fn ask_new_game(&mut self) -> bool {
+        let message = "You are dead.\nDo you want to play another game?\n(y\\n)";
+        self.confirm(message)
+    }
 
-        for &y in y_list.iter() {
-            for &x in x_list.iter() {
-                let i = x + y * self.arena_width;
+    fn show_game_complete_and_ask(&mut self) -> bool {
+        let message =
+            "Congratulations!\nYou completed the game.\nDo you want to play another game?\n(y\\n))";
+        self.confirm(message)
+    }
 
-                if self.arena[i] != ObjectType::None {
-                    continue;
-                }
+    fn confirm(&mut self, message: &str) -> bool {
+        // nobody to ask
+        if self.mode == GameMode::Replay || self.mode == GameMode::Autoplay {
+            return false;
+        }
 
-                let dx = x as i16 - self.player_x as i16;
-                let dy = y as i16 - self.player_y as i16;
-                let moved = self.player_move(dx, dy, safe);
+        self.board.show_message(message);
 
-                if moved {
-                    return true;
-                }
+        loop {
+            match self.next_action(Context::Confirmation) {
+                Some(Action::Yes) => return true,
+                Some(Action::No) | None => return false,
+                _ => {}
             }
         }
-
-        false
     }
 
-    fn waiting(&mut self) {
-        let mut kills = 0;
-        loop {
-            thread::sleep(Duration::from_millis(WAITING_DELAY));
-
-            let prev_score = self.score;
-
-            self.player_move_k(0, 0, false, &mut kills);
+    fn show_help(&mut self) {
+        self.board.show_dialog(&self.options.keys.help());
+        self.next_action(Context::Dialog);
+        self.update_board();
+    }
+}
 
-            self.board.update(&self.arena);
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
 
-            if self.num_robots_per_safe > 0 {
-                while kills >= self.num_robots_per_safe {
-                    self.safeteleports += 1;
-                    self.safeteleports = min(self.safeteleports, self.max_safe_teleports);
-                    kills -= self.num_robots_per_safe;
-                    self.board.set_safeteleports(self.safeteleports);
-                }
-            }
+    use super::*;
 
-            let mut score_step = self.score - prev_score;
-            if self.safe_score_boundary > 0 {
-                while score_step >= self.safe_score_boundary {
-                    self.safeteleports += 1;
-                    self.safeteleports = min(self.safeteleports, self.max_safe_teleports);
-                    score_step -= self.safe_score_boundary;
-                    self.board.set_safeteleports(self.safeteleports);
-                }
-            }
+    struct NullRenderer;
 
-            if self.state != GameState::Waiting {
-                return;
-            }
+    impl Renderer for NullRenderer {
+        fn init(&mut self) -> Result<(), String> {
+            Ok(())
+        }
+        fn close(&mut self) {}
+        fn set_status(&mut self, _level: u16, _score: u64, _safeteleports: u16, _seed: u64) {}
+        fn wants_safe_moves(&self) -> bool {
+            false
+        }
+        fn set_safe_moves(&mut self, _safe_moves: &[PlayerCommand]) {}
+        fn update(&mut self, _arena: &[ObjectType]) {}
+        fn show_player_message(&mut self, _x: usize, _y: usize, _message: &str) {}
+        fn show_message(&mut self, _message: &str) {}
+        fn show_dialog(&mut self, _message: &str) {}
+        fn describe(&mut self) {}
+        fn pan(&mut self, _dx: i16, _dy: i16) {}
+        fn resize(&mut self) -> bool {
+            true
         }
     }
 
-    fn place_randomly(&mut self, object: ObjectType) {
-        let mut rng = thread_rng();
+    // The same actions whatever the game is waiting for
+    struct ScriptedInput(VecDeque<Action>);
 
-        let mut x_list: Vec<usize> = (0..self.arena_width).collect();
-        let mut y_list: Vec<usize> = (0..self.arena_height).collect();
-
-        x_list.shuffle(&mut rng);
-        y_list.shuffle(&mut rng);
-
-        for &y in y_list.iter() {
-            for &x in x_list.iter() {
-                let i = x + y * self.arena_width;
-
-                if self.arena[i] == ObjectType::None {
-                    self.arena[i] = object;
-                    return;
-                }
-            }
+    impl InputSource for ScriptedInput {
+        fn next_action(&mut self, _context: Context, _engine: &Engine) -> Option<Action> {
+            self.0.pop_front()
         }
-
-        panic!("Not able to place randomly");
     }
 
-    fn update_status(
-        &mut self,
-        player_x: usize,
-        player_y: usize,
-        new_arena: Vec<ObjectType>,
-        result: &MoveRobotsResult,
-    ) {
-        self.player_x = player_x;
-        self.player_y = player_y;
-        self.arena = new_arena;
-        self.score += result.score;
-        self.board.set_score(self.score);
-    }
+    // A practice game (nothing is written to disk) played with these actions
+    fn game(options: &mut Options, actions: Vec<Action>) -> Game {
+        options.practice = true;
 
-    fn show_dead(&mut self) {
-        self.board
-            .show_player_message(self.player_x, self.player_y, "AARRrrgghhhh....");
-        thread::sleep(Duration::from_millis(CHANGE_DELAY))
+        let input = Box::new(ScriptedInput(actions.into()));
+        let mut game = Game::with(Box::new(NullRenderer), input, &Keyboard::new(), options);
+        game.animations = false;
+        game
     }
 
-    fn show_level_complete(&mut self) {
-        self.board
-            .show_player_message(self.player_x, self.player_y, "Yahoo!!");
-        thread::sleep(Duration::from_millis(CHANGE_DELAY))
+    // The first seed where waiting for the end gives this state
+    fn seed_where_waiting(options: &Options, state: GameState) -> u64 {
+        (0..)
+            .find(|&seed| {
+                let mut engine = Engine::new(options);
+                engine.new_game(seed);
+                engine.player_command(PlayerCommand::Wait);
+                while engine.state() == GameState::Waiting {
+                    engine.wait_step();
+                }
+                engine.state() == state
+            })
+            .unwrap()
     }
 
-    fn ask_quit(&mut self) -> bool {
-        let message = "Do you really want to quit?\n(y\\n)";
-        self.board.show_confirmation_dialog(message)
-    }
+    #[test]
+    fn dead_and_new_game() {
+        let mut options = Options::default();
+        options.seed = Some(seed_where_waiting(&options, GameState::Dead));
 
-    fn ask_new_game(&mut self) -> bool {
-        let message = "You are dead.\nDo you want to play another game?\n(y\\n)";
-        self.board.show_confirmation_dialog(message)
+        let actions = vec![
+            Action::Command(PlayerCommand::Wait),
+            Action::No,  // undo?
+            Action::Yes, // another game?
+        ];
+        let mut game = game(&mut options, actions);
+        game.new_game();
+        let seed = game.engine.seed();
+        game.run();
+
+        assert_eq!(game.engine.state(), GameState::Playing);
+        assert_eq!(game.engine.score(), 0);
+        assert_ne!(game.engine.seed(), seed);
     }
 
-    fn show_game_complete_and_ask(&mut self) -> bool {
-        let message =
-            "Congratulations!\nYou completed the game.\nDo you want to play another game?\n(y\\n))";
-        self.board.show_confirmation_dialog(message)
+    #[test]
+    fn dead_and_undo() {
+        let mut options = Options::default();
+        options.seed = Some(seed_where_waiting(&options, GameState::Dead));
+
+        let actions = vec![
+            Action::Command(PlayerCommand::Wait),
+            Action::Yes, // undo?
+        ];
+        let mut game = game(&mut options, actions);
+        game.new_game();
+        let arena = game.engine.arena().to_vec();
+        game.run();
+
+        assert_eq!(game.engine.state(), GameState::Playing);
+        assert!(game.engine.arena() == arena);
     }
 
-    fn show_help(&mut self) {
-        self.board.show_dialog(HELP_MESSAGE);
-        self.board.update(&self.arena);
+    #[test]
+    fn undo_and_redo() {
+        let mut options = Options::default();
+        options.seed = Some(1);
+        let mut game = game(&mut options, Vec::new());
+        game.new_game();
+
+        let start = game.engine.arena().to_vec();
+        game.player_command(PlayerCommand::Stay);
+        let after_stay = game.engine.arena().to_vec();
+        assert_eq!(game.engine.state(), GameState::Playing);
+        assert!(after_stay != start);
+
+        game.undo();
+        assert!(game.engine.arena() == start);
+        game.redo();
+        assert!(game.engine.arena() == after_stay);
+        // nothing more to redo
+        game.redo();
+        assert!(game.engine.arena() == after_stay);
+
+        // a new move forgets what was undone
+        game.undo();
+        game.player_command(PlayerCommand::RandomTeleport);
+        let after_teleport = game.engine.arena().to_vec();
+        game.redo();
+        assert!(game.engine.arena() == after_teleport);
+        game.undo();
+        assert!(game.engine.arena() == start);
     }
-}
 
-struct MoveRobotsResult {
-    dead: bool,
-    score: u64,
-    kills: u16,
-}
+    #[test]
+    fn level_complete() {
+        let mut options = Options::default();
+        options.initial_type1 = 2;
+        options.initial_type2 = 0;
+        options.seed = Some(seed_where_waiting(&options, GameState::LevelComplete));
+
+        let actions = vec![
+            Action::Command(PlayerCommand::Wait),
+            Action::Quit,
+            Action::Yes,
+        ];
+        let mut game = game(&mut options, actions);
+        game.new_game();
+        game.run();
 
-impl MoveRobotsResult {
-    pub fn new() -> Self {
-        MoveRobotsResult {
-            dead: false,
-            score: 0,
-            kills: 0,
-        }
+        assert_eq!(game.engine.state(), GameState::Playing);
+        assert_eq!(game.engine.level(), 1);
+        assert!(game.engine.score() > 0);
     }
 }
-
-const HELP_MESSAGE: &str = " Escape from evil robots who want to exterminate you.
-
- Directions:
-
-    7   8   9        y     k     u
-      \\ | /           \\    |   /
-    4 - 5 - 6        h - SPACE - l
-      / | \\           /    |   \\
-    1   2   3        b     j     n
-
- Commands:
-    w          : wait for end
-    + or ENTER : safe teleport
-    - or t     : random teleport
-    q          : quit
-    ?          : this help
-
-";
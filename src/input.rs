@@ -0,0 +1,470 @@
+use std::cell::{Cell, OnceCell, RefCell};
+use std::cmp::{max, min};
+use std::collections::VecDeque;
+use std::io::{stdin, BufRead, Read};
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use signal_hook::consts::SIGWINCH;
+use signal_hook::iterator::Signals;
+use termion::event::{parse_event, Event, Key};
+
+use crate::ai::{self, Strategy};
+use crate::engine::Engine;
+use crate::enums::PlayerCommand;
+use crate::keys::KeyBindings;
+use crate::options::{Options, RendererType};
+
+const REPLAY_DELAY: u64 = 500;
+const REPLAY_MIN_DELAY: u64 = 40;
+const REPLAY_MAX_DELAY: u64 = 5000;
+
+/*
+ * What the player wants to do, whatever device is used
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Command(PlayerCommand),
+    // move on in a direction until something happens
+    Run(PlayerCommand),
+    Quit,
+    Help,
+    // tell everything on the board (accessible mode)
+    Describe,
+    Undo,
+    Redo,
+    Yes,
+    No,
+    Continue,
+    // look at another part of the arena
+    Pan(i16, i16),
+    // the terminal has been resized
+    Resize,
+}
+
+/*
+ * What the game is waiting for (the same key can mean different actions)
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Context {
+    Playing,
+    Confirmation,
+    Dialog,
+}
+
+pub trait InputSource {
+    // The next action, None when there is no more input
+    fn next_action(&mut self, context: Context, engine: &Engine) -> Option<Action>;
+
+    // Waits for the timeout, true as soon as the terminal is resized
+    // (the other input is kept for the next actions)
+    fn resized_before(&mut self, timeout: Duration) -> bool {
+        thread::sleep(timeout);
+        false
+    }
+}
+
+pub fn new_input(options: &Options, keyboard: &Keyboard) -> Box<dyn InputSource> {
+    match options.renderer {
+        RendererType::Terminal => Box::new(KeyboardInput::new(keyboard, &options.keys)),
+        RendererType::Text | RendererType::Accessible => Box::new(LineInput::new(&options.keys)),
+    }
+}
+
+pub fn key_action(key: Key, context: Context, keys: &KeyBindings) -> Option<Action> {
+    match context {
+        Context::Playing => keys.action(key),
+        // the answers are always 'y' and 'n', the quit key is a no
+        Context::Confirmation => match key {
+            Key::Char(c) if c.eq_ignore_ascii_case(&'y') => Some(Action::Yes),
+            Key::Char(c) if c.eq_ignore_ascii_case(&'n') => Some(Action::No),
+            key if keys.action(key) == Some(Action::Quit) => Some(Action::No),
+            _ => None,
+        },
+        Context::Dialog => Some(Action::Continue),
+    }
+}
+
+/*
+ * What the terminal sends
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyboardEvent {
+    Key(Key),
+    // the terminal has been resized (SIGWINCH)
+    Resize,
+    // stdin is closed
+    End,
+}
+
+struct KeyboardEvents {
+    events: OnceCell<Receiver<KeyboardEvent>>,
+    ended: Cell<bool>,
+    // the keys pressed while waiting for a resize
+    pending: RefCell<VecDeque<KeyboardEvent>>,
+}
+
+/*
+ * The only reader of the keyboard, shared by every input source of the
+ * process so that no key is lost when a source replaces another.
+ * It starts reading stdin when it is first used: the text renderers and
+ * the bots read stdin themselves.
+ */
+#[derive(Clone)]
+pub struct Keyboard {
+    shared: Rc<KeyboardEvents>,
+}
+
+impl Keyboard {
+    pub fn new() -> Self {
+        Keyboard {
+            shared: Rc::new(KeyboardEvents {
+                events: OnceCell::new(),
+                ended: Cell::new(false),
+                pending: RefCell::new(VecDeque::new()),
+            }),
+        }
+    }
+
+    fn events(&self) -> &Receiver<KeyboardEvent> {
+        self.shared.events.get_or_init(|| {
+            let (sender, events) = mpsc::channel();
+
+            let keys = sender.clone();
+            thread::spawn(move || read_keys(keys));
+
+            if let Ok(mut signals) = Signals::new([SIGWINCH]) {
+                thread::spawn(move || {
+                    for _ in signals.forever() {
+                        if sender.send(KeyboardEvent::Resize).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+
+            events
+        })
+    }
+
+    // The next event, waiting as long as needed
+    pub fn next(&self) -> KeyboardEvent {
+        if let Some(event) = self.shared.pending.borrow_mut().pop_front() {
+            return event;
+        }
+        if self.shared.ended.get() {
+            return KeyboardEvent::End;
+        }
+        let event = self.events().recv().unwrap_or(KeyboardEvent::End);
+        if event == KeyboardEvent::End {
+            self.shared.ended.set(true);
+        }
+        event
+    }
+
+    // The next event before the timeout, None when the time is over
+    // (without stdin, the time is simply waited)
+    pub fn next_before(&self, timeout: Duration) -> Option<KeyboardEvent> {
+        if let Some(event) = self.shared.pending.borrow_mut().pop_front() {
+            return Some(event);
+        }
+        self.receive_before(timeout)
+    }
+
+    // Waits for the timeout but stops when the terminal is resized,
+    // the keys pressed meanwhile are kept for later
+    pub fn resized_before(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            match self.receive_before(deadline.saturating_duration_since(Instant::now())) {
+                Some(KeyboardEvent::Resize) => return true,
+                Some(event) => self.shared.pending.borrow_mut().push_back(event),
+                None => return false,
+            }
+        }
+    }
+
+    fn receive_before(&self, timeout: Duration) -> Option<KeyboardEvent> {
+        if !self.shared.ended.get() {
+            match self.events().recv_timeout(timeout) {
+                Ok(KeyboardEvent::End) | Err(RecvTimeoutError::Disconnected) => {
+                    self.shared.ended.set(true)
+                }
+                Ok(event) => return Some(event),
+                Err(RecvTimeoutError::Timeout) => return None,
+            }
+        }
+        thread::sleep(timeout);
+        None
+    }
+}
+
+/*
+ * Every read of stdin is decoded on its own, so that a lone ESC is not
+ * mistaken for the beginning of the next key
+ */
+fn read_keys(keys: Sender<KeyboardEvent>) {
+    let mut stdin = stdin();
+    let mut buffer = [0; 64];
+
+    while let Ok(n) = stdin.read(&mut buffer) {
+        if n == 0 {
+            break;
+        }
+        let mut bytes = buffer[..n].iter().map(|&byte| Ok(byte));
+        while let Some(Ok(byte)) = bytes.next() {
+            if let Ok(Event::Key(key)) = parse_event(byte, &mut bytes) {
+                if keys.send(KeyboardEvent::Key(key)).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    let _ = keys.send(KeyboardEvent::End);
+}
+
+/*
+ * The keyboard of a terminal in raw mode
+ */
+pub struct KeyboardInput {
+    keyboard: Keyboard,
+    bindings: KeyBindings,
+}
+
+impl KeyboardInput {
+    pub fn new(keyboard: &Keyboard, bindings: &KeyBindings) -> Self {
+        KeyboardInput {
+            keyboard: keyboard.clone(),
+            bindings: bindings.clone(),
+        }
+    }
+}
+
+impl InputSource for KeyboardInput {
+    fn resized_before(&mut self, timeout: Duration) -> bool {
+        self.keyboard.resized_before(timeout)
+    }
+
+    fn next_action(&mut self, context: Context, _engine: &Engine) -> Option<Action> {
+        loop {
+            match self.keyboard.next() {
+                KeyboardEvent::Key(key) => {
+                    if let Some(action) = key_action(key, context, &self.bindings) {
+                        return Some(action);
+                    }
+                }
+                KeyboardEvent::Resize => return Some(Action::Resize),
+                KeyboardEvent::End => return None,
+            }
+        }
+    }
+}
+
+/*
+ * Input read a line at a time, for pipes and dumb terminals:
+ * every character is a key and an empty line is ENTER
+ */
+pub struct LineInput {
+    pending_keys: VecDeque<Key>,
+    bindings: KeyBindings,
+}
+
+impl LineInput {
+    pub fn new(bindings: &KeyBindings) -> Self {
+        LineInput {
+            pending_keys: VecDeque::new(),
+            bindings: bindings.clone(),
+        }
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        let mut line = String::new();
+        match BufRead::read_line(&mut stdin().lock(), &mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line.trim_end_matches(&['\r', '\n'][..]).to_string()),
+        }
+    }
+}
+
+impl InputSource for LineInput {
+    fn next_action(&mut self, context: Context, _engine: &Engine) -> Option<Action> {
+        // answers are given on a new line, only its first character counts
+        if context != Context::Playing {
+            self.pending_keys.clear();
+            loop {
+                let line = self.read_line()?;
+                let key = match line.trim().chars().next() {
+                    Some(c) => Key::Char(c),
+                    None => Key::Char('\n'),
+                };
+                if let Some(action) = key_action(key, context, &self.bindings) {
+                    return Some(action);
+                }
+            }
+        }
+
+        loop {
+            if self.pending_keys.is_empty() {
+                let line = self.read_line()?;
+                if line.is_empty() {
+                    self.pending_keys.push_back(Key::Char('\n'));
+                }
+                self.pending_keys.extend(line.chars().map(Key::Char));
+            }
+
+            let key = self.pending_keys.pop_front()?;
+            if let Some(action) = key_action(key, context, &self.bindings) {
+                return Some(action);
+            }
+        }
+    }
+}
+
+/*
+ * The commands of a recorded game, one at a time.
+ * SPACE pauses, 'n' or '.' advances of a single command,
+ * '+' and '-' change the speed and the quit key stops the replay.
+ */
+pub struct ReplayInput {
+    keyboard: Keyboard,
+    bindings: KeyBindings,
+    commands: VecDeque<PlayerCommand>,
+
+    delay: u64,
+    // when the current command started to wait for its turn
+    waiting_since: Option<Instant>,
+    paused: bool,
+}
+
+impl ReplayInput {
+    pub fn new(keyboard: &Keyboard, bindings: &KeyBindings, commands: &[PlayerCommand]) -> Self {
+        ReplayInput {
+            keyboard: keyboard.clone(),
+            bindings: bindings.clone(),
+            commands: commands.iter().copied().collect(),
+
+            delay: REPLAY_DELAY,
+            waiting_since: None,
+            paused: false,
+        }
+    }
+
+    fn next_command(&mut self) -> Option<Action> {
+        self.waiting_since = None;
+        self.commands.pop_front().map(Action::Command)
+    }
+}
+
+impl InputSource for ReplayInput {
+    fn resized_before(&mut self, timeout: Duration) -> bool {
+        self.keyboard.resized_before(timeout)
+    }
+
+    fn next_action(&mut self, context: Context, _engine: &Engine) -> Option<Action> {
+        match context {
+            Context::Playing => loop {
+                let event = if self.paused {
+                    self.keyboard.next()
+                } else {
+                    let since = *self.waiting_since.get_or_insert_with(Instant::now);
+                    let deadline = since + Duration::from_millis(self.delay);
+                    match self
+                        .keyboard
+                        .next_before(deadline.saturating_duration_since(Instant::now()))
+                    {
+                        Some(event) => event,
+                        None => return self.next_command(),
+                    }
+                };
+
+                match event {
+                    KeyboardEvent::Key(key) if self.bindings.action(key) == Some(Action::Quit) => {
+                        return Some(Action::Quit)
+                    }
+                    KeyboardEvent::Key(Key::Char(c)) => match c.to_ascii_lowercase() {
+                        ' ' => self.paused = !self.paused,
+                        'n' | '.' => {
+                            self.paused = true;
+                            return self.next_command();
+                        }
+                        '+' => self.delay = max(self.delay / 2, REPLAY_MIN_DELAY),
+                        '-' => self.delay = min(self.delay * 2, REPLAY_MAX_DELAY),
+                        _ => {}
+                    },
+                    KeyboardEvent::Key(_) => {}
+                    KeyboardEvent::Resize => return Some(Action::Resize),
+                    // nobody can resume a paused replay any more
+                    KeyboardEvent::End => self.paused = false,
+                }
+            },
+            // the replay never plays another game
+            Context::Confirmation => Some(Action::No),
+            Context::Dialog => match self.keyboard.next() {
+                KeyboardEvent::Key(_) | KeyboardEvent::End => Some(Action::Continue),
+                KeyboardEvent::Resize => Some(Action::Resize),
+            },
+        }
+    }
+}
+
+/*
+ * A built-in player, the quit key stops it
+ */
+pub struct AutoplayInput {
+    keyboard: Keyboard,
+    bindings: KeyBindings,
+    strategy: Strategy,
+    delay: u64,
+}
+
+impl AutoplayInput {
+    pub fn new(
+        keyboard: &Keyboard,
+        bindings: &KeyBindings,
+        strategy: Strategy,
+        delay: u64,
+    ) -> Self {
+        AutoplayInput {
+            keyboard: keyboard.clone(),
+            bindings: bindings.clone(),
+            strategy,
+            delay,
+        }
+    }
+}
+
+impl InputSource for AutoplayInput {
+    fn resized_before(&mut self, timeout: Duration) -> bool {
+        self.keyboard.resized_before(timeout)
+    }
+
+    fn next_action(&mut self, context: Context, engine: &Engine) -> Option<Action> {
+        match context {
+            Context::Playing => {
+                let deadline = Instant::now() + Duration::from_millis(self.delay);
+
+                loop {
+                    match self
+                        .keyboard
+                        .next_before(deadline.saturating_duration_since(Instant::now()))
+                    {
+                        Some(KeyboardEvent::Key(key))
+                            if self.bindings.action(key) == Some(Action::Quit) =>
+                        {
+                            return Some(Action::Quit)
+                        }
+                        Some(KeyboardEvent::Resize) => return Some(Action::Resize),
+                        Some(_) => {}
+                        None => return Some(Action::Command(ai::choose(engine, self.strategy))),
+                    }
+                }
+            }
+            Context::Confirmation => Some(Action::No),
+            Context::Dialog => Some(Action::Continue),
+        }
+    }
+}
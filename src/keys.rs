@@ -0,0 +1,551 @@
+use serde::{Deserialize, Serialize};
+use termion::event::Key;
+
+use crate::enums::PlayerCommand;
+use crate::input::Action;
+
+// How many sets of direction keys the help shows side by side
+const MAX_COMPASSES: usize = 3;
+
+/*
+ * The keys of every action while playing, by name: a character, SPACE, ENTER,
+ * TAB, ESC, BACKSPACE, LEFT, RIGHT, UP, DOWN, HOME, END, PAGEUP, PAGEDOWN,
+ * INSERT, DELETE, F1 to F12, CTRL+x or ALT+x.
+ * The actions missing in the configuration keep their default keys.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub nw: Vec<String>,
+    pub n: Vec<String>,
+    pub ne: Vec<String>,
+    pub w: Vec<String>,
+    pub stay: Vec<String>,
+    pub e: Vec<String>,
+    pub sw: Vec<String>,
+    pub s: Vec<String>,
+    pub se: Vec<String>,
+
+    pub run_nw: Vec<String>,
+    pub run_n: Vec<String>,
+    pub run_ne: Vec<String>,
+    pub run_w: Vec<String>,
+    pub run_e: Vec<String>,
+    pub run_sw: Vec<String>,
+    pub run_s: Vec<String>,
+    pub run_se: Vec<String>,
+
+    pub safe_teleport: Vec<String>,
+    pub random_teleport: Vec<String>,
+    pub wait: Vec<String>,
+    pub quit: Vec<String>,
+    pub help: Vec<String>,
+    pub describe: Vec<String>,
+    pub undo: Vec<String>,
+    pub redo: Vec<String>,
+
+    pub look_left: Vec<String>,
+    pub look_right: Vec<String>,
+    pub look_up: Vec<String>,
+    pub look_down: Vec<String>,
+}
+
+fn names(keys: &[&str]) -> Vec<String> {
+    keys.iter().map(|key| key.to_string()).collect()
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            nw: names(&["7", "y"]),
+            n: names(&["8", "k"]),
+            ne: names(&["9", "u"]),
+            w: names(&["4", "h"]),
+            stay: names(&["5", "SPACE"]),
+            e: names(&["6", "l"]),
+            sw: names(&["1", "b"]),
+            s: names(&["2", "j"]),
+            se: names(&["3", "n"]),
+
+            run_nw: names(&["Y"]),
+            run_n: names(&["K"]),
+            run_ne: names(&["U"]),
+            run_w: names(&["H"]),
+            run_e: names(&["L"]),
+            run_sw: names(&["B"]),
+            run_s: names(&["J"]),
+            run_se: names(&["N"]),
+
+            safe_teleport: names(&["+", "ENTER"]),
+            random_teleport: names(&["-", "t"]),
+            wait: names(&["w"]),
+            quit: names(&["q"]),
+            help: names(&["?"]),
+            describe: names(&["d"]),
+            undo: names(&["z"]),
+            redo: names(&["x"]),
+
+            look_left: names(&["LEFT"]),
+            look_right: names(&["RIGHT"]),
+            look_up: names(&["UP"]),
+            look_down: names(&["DOWN"]),
+        }
+    }
+}
+
+impl KeyBindings {
+    // Every action with its name in the configuration and its keys
+    fn bindings(&self) -> [(&'static str, Action, &Vec<String>); 29] {
+        [
+            ("nw", Action::Command(PlayerCommand::NW), &self.nw),
+            ("n", Action::Command(PlayerCommand::N), &self.n),
+            ("ne", Action::Command(PlayerCommand::NE), &self.ne),
+            ("w", Action::Command(PlayerCommand::W), &self.w),
+            ("stay", Action::Command(PlayerCommand::Stay), &self.stay),
+            ("e", Action::Command(PlayerCommand::E), &self.e),
+            ("sw", Action::Command(PlayerCommand::SW), &self.sw),
+            ("s", Action::Command(PlayerCommand::S), &self.s),
+            ("se", Action::Command(PlayerCommand::SE), &self.se),
+            ("run_nw", Action::Run(PlayerCommand::NW), &self.run_nw),
+            ("run_n", Action::Run(PlayerCommand::N), &self.run_n),
+            ("run_ne", Action::Run(PlayerCommand::NE), &self.run_ne),
+            ("run_w", Action::Run(PlayerCommand::W), &self.run_w),
+            ("run_e", Action::Run(PlayerCommand::E), &self.run_e),
+            ("run_sw", Action::Run(PlayerCommand::SW), &self.run_sw),
+            ("run_s", Action::Run(PlayerCommand::S), &self.run_s),
+            ("run_se", Action::Run(PlayerCommand::SE), &self.run_se),
+            (
+                "safe_teleport",
+                Action::Command(PlayerCommand::SafeTeleport),
+                &self.safe_teleport,
+            ),
+            (
+                "random_teleport",
+                Action::Command(PlayerCommand::RandomTeleport),
+                &self.random_teleport,
+            ),
+            ("wait", Action::Command(PlayerCommand::Wait), &self.wait),
+            ("quit", Action::Quit, &self.quit),
+            ("help", Action::Help, &self.help),
+            ("describe", Action::Describe, &self.describe),
+            ("undo", Action::Undo, &self.undo),
+            ("redo", Action::Redo, &self.redo),
+            ("look_left", Action::Pan(-1, 0), &self.look_left),
+            ("look_right", Action::Pan(1, 0), &self.look_right),
+            ("look_up", Action::Pan(0, -1), &self.look_up),
+            ("look_down", Action::Pan(0, 1), &self.look_down),
+        ]
+    }
+
+    // The action of a key while playing, a capital letter is the lowercase one
+    // unless it is bound by itself
+    pub fn action(&self, key: Key) -> Option<Action> {
+        let bound = |key: Key| {
+            self.bindings()
+                .into_iter()
+                .find(|(_, _, keys)| keys.iter().any(|name| parse_key(name) == Ok(key)))
+                .map(|(_, action, _)| action)
+        };
+
+        match key {
+            Key::Char(c) if c.is_ascii_uppercase() => {
+                bound(key).or_else(|| bound(Key::Char(c.to_ascii_lowercase())))
+            }
+            _ => bound(key),
+        }
+    }
+
+    // The problems of these bindings, one per line
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let mut bound: Vec<(Key, &str)> = Vec::new();
+
+        for (name, action, keys) in self.bindings() {
+            // running is not needed to play
+            if keys.is_empty() && !matches!(action, Action::Run(_)) {
+                errors.push(format!("keys.{}: no key", name));
+            }
+            for key_name in keys {
+                match parse_key(key_name) {
+                    Err(e) => errors.push(format!("keys.{}: {}", name, e)),
+                    Ok(key) => match bound.iter().find(|(other_key, _)| *other_key == key) {
+                        Some((_, other)) => errors.push(format!(
+                            "keys.{}: '{}' is already used by {}",
+                            name, key_name, other
+                        )),
+                        None => bound.push((key, name)),
+                    },
+                }
+            }
+        }
+
+        errors
+    }
+
+    // The help of the game, with these keys
+    pub fn help(&self) -> String {
+        let mut text = String::from(" Escape from evil robots who want to exterminate you.\n\n");
+
+        let directions = [
+            &self.nw, &self.n, &self.ne, &self.w, &self.stay, &self.e, &self.sw, &self.s, &self.se,
+        ];
+        let sets = directions.iter().map(|keys| keys.len()).max().unwrap_or(0);
+        let mut compasses: Vec<[String; 5]> = (0..sets.min(MAX_COMPASSES))
+            .map(|i| {
+                compass(
+                    directions
+                        .map(|keys| keys.get(i).map_or(String::new(), |name| display_name(name))),
+                )
+            })
+            .collect();
+        let mut header = " Directions:".to_string();
+
+        // the keys of the runs (without stay) on the right
+        let runs = [
+            &self.run_nw,
+            &self.run_n,
+            &self.run_ne,
+            &self.run_w,
+            &Vec::new(),
+            &self.run_e,
+            &self.run_sw,
+            &self.run_s,
+            &self.run_se,
+        ];
+        if runs.iter().any(|keys| !keys.is_empty()) {
+            let column = compasses
+                .iter()
+                .map(|compass| compass[0].chars().count() + 8)
+                .sum::<usize>()
+                + 4;
+            header = format!("{:<column$}Run:", header);
+            compasses.push(compass(runs.map(|keys| main_key(keys))));
+        }
+
+        text += &header;
+        text += "\n\n";
+        for row in 0..5 {
+            let line: Vec<&str> = compasses
+                .iter()
+                .map(|compass| compass[row].as_str())
+                .collect();
+            text += format!("    {}", line.join("        ")).trim_end();
+            text.push('\n');
+        }
+
+        let look = [
+            &self.look_left,
+            &self.look_right,
+            &self.look_up,
+            &self.look_down,
+        ];
+        let arrows = look
+            .iter()
+            .zip([Key::Left, Key::Right, Key::Up, Key::Down])
+            .all(|(keys, arrow)| keys.len() == 1 && parse_key(&keys[0]) == Ok(arrow));
+        let look_keys = if arrows {
+            "arrows".to_string()
+        } else {
+            join_keys(&look)
+        };
+
+        let commands = [
+            (join_keys(&[&self.wait]), "wait for end"),
+            (join_keys(&[&self.safe_teleport]), "safe teleport"),
+            (join_keys(&[&self.random_teleport]), "random teleport"),
+            (join_keys(&[&self.quit]), "quit"),
+            (
+                join_keys(&[&self.help, &self.describe]),
+                "this help / describe the board",
+            ),
+            (
+                join_keys(&[&self.undo, &self.redo]),
+                "undo / redo (practice mode)",
+            ),
+            (look_keys, "look around (large boards)"),
+        ];
+        let width = commands
+            .iter()
+            .map(|(keys, _)| keys.chars().count())
+            .max()
+            .unwrap_or(0)
+            .max(10);
+
+        text += "\n Commands:\n";
+        for (keys, description) in commands {
+            text += &format!("    {:<width$} : {}\n", keys, description, width = width);
+        }
+        text.push('\n');
+
+        text
+    }
+
+    // The directions of the BSD sidebar, a character for each one
+    pub fn short_compass(&self) -> [String; 5] {
+        let [nw, n, ne, w, e, sw, s, se] = [
+            &self.nw, &self.n, &self.ne, &self.w, &self.e, &self.sw, &self.s, &self.se,
+        ]
+        .map(|keys| short_key(keys));
+
+        [
+            format!("{} {} {}", nw, n, ne),
+            " \\!/".to_string(),
+            format!("{}- -{}", w, e),
+            " /|\\".to_string(),
+            format!("{} {} {}", sw, s, se),
+        ]
+    }
+}
+
+// The name of a key in the configuration
+pub fn parse_key(name: &str) -> Result<Key, String> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Ok(Key::Char(c));
+    }
+
+    // the character after a prefix, as it is written
+    let char_after = |prefix: &str| -> Option<char> {
+        let mut chars = name.get(prefix.len()..)?.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    };
+
+    let p = name.to_uppercase();
+    let key = match p.as_str() {
+        "SPACE" => Key::Char(' '),
+        "ENTER" => Key::Char('\n'),
+        "TAB" => Key::Char('\t'),
+        "ESC" => Key::Esc,
+        "BACKSPACE" => Key::Backspace,
+        "LEFT" => Key::Left,
+        "RIGHT" => Key::Right,
+        "UP" => Key::Up,
+        "DOWN" => Key::Down,
+        "HOME" => Key::Home,
+        "END" => Key::End,
+        "PAGEUP" => Key::PageUp,
+        "PAGEDOWN" => Key::PageDown,
+        "INSERT" => Key::Insert,
+        "DELETE" => Key::Delete,
+        _ => {
+            let function = p
+                .strip_prefix('F')
+                .and_then(|n| n.parse::<u8>().ok())
+                .filter(|n| (1..=12).contains(n));
+
+            if let Some(n) = function {
+                Key::F(n)
+            } else if let Some(c) = p
+                .starts_with("CTRL+")
+                .then(|| char_after("CTRL+"))
+                .flatten()
+            {
+                Key::Ctrl(c.to_ascii_lowercase())
+            } else if let Some(c) = p.starts_with("ALT+").then(|| char_after("ALT+")).flatten() {
+                Key::Alt(c)
+            } else {
+                return Err(format!("'{}' is not a valid key", name));
+            }
+        }
+    };
+
+    Ok(key)
+}
+
+// How a key is called in the help
+pub fn key_name(key: Key) -> String {
+    match key {
+        Key::Char(' ') => "SPACE".to_string(),
+        Key::Char('\n') => "ENTER".to_string(),
+        Key::Char('\t') => "TAB".to_string(),
+        Key::Char(c) => c.to_string(),
+        Key::Esc => "ESC".to_string(),
+        Key::Backspace => "BACKSPACE".to_string(),
+        Key::Left => "LEFT".to_string(),
+        Key::Right => "RIGHT".to_string(),
+        Key::Up => "UP".to_string(),
+        Key::Down => "DOWN".to_string(),
+        Key::Home => "HOME".to_string(),
+        Key::End => "END".to_string(),
+        Key::PageUp => "PAGEUP".to_string(),
+        Key::PageDown => "PAGEDOWN".to_string(),
+        Key::Insert => "INSERT".to_string(),
+        Key::Delete => "DELETE".to_string(),
+        Key::F(n) => format!("F{}", n),
+        Key::Ctrl(c) => format!("CTRL+{}", c.to_ascii_uppercase()),
+        Key::Alt(c) => format!("ALT+{}", c),
+        _ => "?".to_string(),
+    }
+}
+
+// The name of a key in the configuration as it is called in the help
+fn display_name(name: &str) -> String {
+    parse_key(name).map_or(name.to_string(), key_name)
+}
+
+// The first key of an action, the one told in short messages
+pub fn main_key(keys: &[String]) -> String {
+    keys.first()
+        .map_or(String::new(), |name| display_name(name))
+}
+
+// e.g. "+ or ENTER" for an action, "z / x" for two
+fn join_keys(actions: &[&Vec<String>]) -> String {
+    actions
+        .iter()
+        .map(|keys| {
+            keys.iter()
+                .map(|name| display_name(name))
+                .collect::<Vec<String>>()
+                .join(" or ")
+        })
+        .collect::<Vec<String>>()
+        .join(" / ")
+}
+
+// A key of a single character, better a letter than a digit of the numeric keypad
+fn short_key(keys: &[String]) -> char {
+    let chars: Vec<char> = keys
+        .iter()
+        .filter_map(|name| match parse_key(name) {
+            Ok(Key::Char(c)) if c == ' ' || c.is_ascii_graphic() => Some(c),
+            _ => None,
+        })
+        .collect();
+
+    chars
+        .iter()
+        .find(|c| !c.is_ascii_digit())
+        .or(chars.first())
+        .copied()
+        .unwrap_or(' ')
+}
+
+/*
+ * The keys of the nine directions drawn as a compass:
+ *
+ *    7   8   9
+ *      \ | /
+ *    4 - 5 - 6
+ *      / | \
+ *    1   2   3
+ */
+fn compass(keys: [String; 9]) -> [String; 5] {
+    let [nw, n, ne, w, stay, e, sw, s, se] = keys;
+
+    let width = |column: [&String; 3]| column.iter().map(|key| key.chars().count()).max().unwrap();
+    let left = width([&nw, &w, &sw]);
+    let center = width([&n, &stay, &s]).max(1);
+    let right = width([&ne, &e, &se]);
+
+    // a line goes to every key there is
+    let lines = |first: &String, middle: &String, last: &String, (a, b, c): (char, char, char)| {
+        let mut row = vec![' '; left + center + right + 6];
+        for (key, column, line) in [
+            (first, left + 1, a),
+            (middle, left + 3 + (center - 1) / 2, b),
+            (last, left + center + 4, c),
+        ] {
+            if !key.is_empty() {
+                row[column] = line;
+            }
+        }
+        row.into_iter().collect::<String>()
+    };
+    let dash = |key: &String| if key.is_empty() { ' ' } else { '-' };
+
+    [
+        format!("{:>left$}   {:^center$}   {:<right$}", nw, n, ne),
+        lines(&nw, &n, &ne, ('\\', '|', '/')),
+        format!(
+            "{:>left$} {} {:^center$} {} {:<right$}",
+            w,
+            dash(&w),
+            stay,
+            dash(&e),
+            e
+        ),
+        lines(&sw, &s, &se, ('/', '|', '\\')),
+        format!("{:>left$}   {:^center$}   {:<right$}", sw, s, se),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{self, Context};
+
+    #[test]
+    fn bindings() {
+        for name in [
+            "k", "K", "SPACE", "ENTER", "LEFT", "PAGEUP", "F5", "CTRL+A", "ALT+x",
+        ] {
+            assert_eq!(key_name(parse_key(name).unwrap()), name);
+        }
+        assert_eq!(parse_key("space"), Ok(Key::Char(' ')));
+        assert_eq!(parse_key("ctrl+a"), Ok(Key::Ctrl('a')));
+        assert!(parse_key("F13").is_err());
+        assert!(parse_key("CTRL+").is_err());
+
+        let mut keys = KeyBindings::default();
+        assert!(keys.validate().is_empty());
+        assert_eq!(
+            keys.action(Key::Char('K')),
+            Some(Action::Run(PlayerCommand::N))
+        );
+        assert_eq!(
+            keys.action(Key::Char('W')),
+            Some(Action::Command(PlayerCommand::Wait))
+        );
+        assert_eq!(keys.action(Key::Up), Some(Action::Pan(0, -1)));
+        let help = keys.help();
+        let help: Vec<&str> = help.lines().skip(2).take(7).collect();
+        assert_eq!(
+            help,
+            [
+                " Directions:                              Run:",
+                "",
+                "    7   8   9        y     k     u        Y   K   U",
+                "      \\ | /            \\   |   /            \\ | /",
+                "    4 - 5 - 6        h - SPACE - l        H -   - L",
+                "      / | \\            /   |   \\            / | \\",
+                "    1   2   3        b     j     n        B   J   N",
+            ]
+        );
+
+        // the arrows move instead of looking around
+        keys.n = names(&["8", "k", "UP"]);
+        keys.look_up = names(&["PAGEUP"]);
+        keys.quit = Vec::new();
+        keys.wait = names(&["w", "?", "BIG"]);
+        assert_eq!(
+            keys.action(Key::Up),
+            Some(Action::Command(PlayerCommand::N))
+        );
+        assert_eq!(
+            keys.validate(),
+            [
+                "keys.wait: 'BIG' is not a valid key",
+                "keys.quit: no key",
+                "keys.help: '?' is already used by wait",
+            ]
+        );
+    }
+
+    #[test]
+    fn confirmation() {
+        let keys = KeyBindings {
+            quit: names(&["ESC"]),
+            ..KeyBindings::default()
+        };
+
+        let answer = |key| input::key_action(key, Context::Confirmation, &keys);
+        assert_eq!(answer(Key::Char('Y')), Some(Action::Yes));
+        assert_eq!(answer(Key::Char('n')), Some(Action::No));
+        assert_eq!(answer(Key::Esc), Some(Action::No));
+        assert_eq!(answer(Key::Char('q')), None);
+    }
+}
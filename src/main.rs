@@ -1,28 +1,140 @@
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::io::{self, Write};
+use std::panic;
 use std::process;
+use std::sync::Mutex;
 
+mod accessibleboard;
+mod ai;
 mod argparse;
 mod board;
+mod bot;
+mod engine;
 mod enums;
 mod game;
+mod input;
+mod keys;
 mod options;
 mod profiles;
+mod recording;
+mod renderer;
+mod savegame;
+mod scores;
+mod simulator;
+mod textboard;
+mod themes;
+
+// A panic is reported when the terminal has been given back, not on the game screen
+static PANIC_MESSAGE: Mutex<Option<String>> = Mutex::new(None);
 
 fn main() {
-    let mut options = options::Options::load();
+    let mut options = options::Options::default();
+
+    let mode = argparse::parse_args(&mut options);
 
-    argparse::parse_args(&mut options);
+    panic::set_hook(Box::new(|info| {
+        let mut message = format!("daleks {}", info);
+        let backtrace = Backtrace::capture();
+        if backtrace.status() == BacktraceStatus::Captured {
+            message.push_str(&format!("\nstack backtrace:\n{}", backtrace));
+        }
+        if let Ok(mut panic_message) = PANIC_MESSAGE.lock() {
+            *panic_message = Some(message);
+        }
+    }));
+
+    // unwinding drops the board, which restores the terminal
+    if panic::catch_unwind(|| run(&options, mode)).is_err() {
+        io::stdout().flush().unwrap();
+        if let Some(message) = PANIC_MESSAGE.lock().ok().and_then(|mut m| m.take()) {
+            eprintln!("{}", message);
+        }
+        process::exit(101);
+    }
+}
 
-    let res = game::Game::new(&options);
+fn run(options: &options::Options, mode: argparse::Mode) {
+    // the only reader of the keyboard, whatever the number of games
+    let keyboard = input::Keyboard::new();
 
-    match res {
-        Ok(mut game) => {
-            game.new_game();
-            game.run();
+    match mode {
+        argparse::Mode::Play => play(options, &keyboard),
+        argparse::Mode::Replay(path) => replay(options, &keyboard, &path),
+        argparse::Mode::Autoplay(strategy, delay) => autoplay(options, &keyboard, strategy, delay),
+        argparse::Mode::Simulate(games, strategy, profile) => {
+            simulator::run(options, games, strategy, profile)
         }
-        Err(s) => {
-            eprintln!("ERROR: {}", s);
+        argparse::Mode::Bot => bot::run(options, io::stdin().lock(), io::stdout().lock()),
+    }
+}
+
+fn play(options: &options::Options, keyboard: &input::Keyboard) {
+    match savegame::SavedGame::load() {
+        Ok(Some(saved)) => {
+            let resume_options = with_look_of(&saved.options, options);
+            let mut game =
+                game::Game::new(&resume_options, keyboard).unwrap_or_else(|s| exit_with_error(&s));
 
-            process::exit(1);
+            // the saved game is kept until it is resumed or refused
+            if game.ask_resume() {
+                game.resume(saved);
+                savegame::SavedGame::remove();
+                game.run();
+                return;
+            }
+            savegame::SavedGame::remove();
         }
+        Ok(None) => {}
+        // it stays there, a new game quit with 'q' takes its place
+        Err(e) => warn(&format!("Cannot resume the saved game, {}", e)),
     }
+
+    let mut game = game::Game::new(options, keyboard).unwrap_or_else(|s| exit_with_error(&s));
+
+    game.new_game();
+    game.run();
+}
+
+fn autoplay(
+    options: &options::Options,
+    keyboard: &input::Keyboard,
+    strategy: ai::Strategy,
+    delay: u64,
+) {
+    let mut game = game::Game::new(options, keyboard).unwrap_or_else(|s| exit_with_error(&s));
+
+    game.autoplay(strategy, delay);
+}
+
+fn replay(options: &options::Options, keyboard: &input::Keyboard, path: &str) {
+    let recording = recording::Recording::load(path).unwrap_or_else(|s| exit_with_error(&s));
+
+    let replay_options = with_look_of(&recording.options, options);
+    let mut game =
+        game::Game::new(&replay_options, keyboard).unwrap_or_else(|s| exit_with_error(&s));
+
+    game.replay(&recording);
+}
+
+// the rules come from a saved game or a recording, the look from the current options
+fn with_look_of(rules: &options::Options, look: &options::Options) -> options::Options {
+    let mut options = rules.clone();
+    options.colors = look.colors;
+    options.asciionly = look.asciionly;
+    options.theme = look.theme.clone();
+    options.keys = look.keys.clone();
+    options.renderer = look.renderer;
+    options
+}
+
+fn warn(s: &str) {
+    eprintln!("WARNING: {}", s);
+}
+
+fn exit_with_error(s: &str) -> ! {
+    // the way back from the alternate screen could still be buffered
+    io::stdout().flush().unwrap();
+    eprintln!("ERROR: {}", s);
+
+    process::exit(1);
 }
@@ -1,31 +1,62 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 
+use termion::terminal_size;
+
+use crate::keys::KeyBindings;
 use crate::profiles;
+use crate::themes;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum BoardSize {
+    Small,
+    #[default]
     Normal,
+    Large,
+    Huge,
+    // as large as the terminal when the game starts
+    Auto,
+    // width, height
+    Custom(usize, usize),
 }
 
-impl Default for BoardSize {
-    fn default() -> Self {
-        BoardSize::Normal
+// The smallest board, because of the BSD sidebar and the dialogs
+pub const MIN_BOARD_SIZE: (usize, usize) = (60, 24);
+// The largest one, the coordinates of the engine are i16
+pub const MAX_BOARD_SIZE: (usize, usize) = (1000, 1000);
+
+impl std::str::FromStr for BoardSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let p = s.to_uppercase();
+        match p.as_str() {
+            "SMALL" => Ok(BoardSize::Small),
+            "NORMAL" => Ok(BoardSize::Normal),
+            "LARGE" => Ok(BoardSize::Large),
+            "HUGE" => Ok(BoardSize::Huge),
+            "AUTO" => Ok(BoardSize::Auto),
+            _ => match p.split_once('X') {
+                Some((width, height)) => match (width.parse(), height.parse()) {
+                    (Ok(width), Ok(height)) => Ok(BoardSize::Custom(width, height)),
+                    _ => Err(format!("'{}' is not a valid value for BoardSize", s)),
+                },
+                None => Err(format!("'{}' is not a valid value for BoardSize", s)),
+            },
+        }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum BoardType {
+    #[default]
     Normal,
     Bsd,
 }
 
-impl Default for BoardType {
-    fn default() -> Self {
-        BoardType::Normal
-    }
-}
-
 impl std::str::FromStr for BoardType {
     type Err = String;
 
@@ -39,13 +70,39 @@ impl std::str::FromStr for BoardType {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RendererType {
+    #[default]
+    Terminal,
+    Text,
+    Accessible,
+}
+
+impl std::str::FromStr for RendererType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let p = s.to_uppercase();
+        match p.as_str() {
+            "TERMINAL" => Ok(RendererType::Terminal),
+            "TEXT" => Ok(RendererType::Text),
+            "ACCESSIBLE" => Ok(RendererType::Accessible),
+            _ => Err(format!("'{}' is not a valid value for RendererType", s)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct Options {
     pub boardsize: BoardSize,
     pub boardtype: BoardType,
+    pub renderer: RendererType,
 
     pub asciionly: bool,
     pub colors: bool,
+    pub theme: String,
+    #[serde(default)]
+    pub keys: KeyBindings,
 
     pub safe_moves: bool,
 
@@ -67,6 +124,16 @@ pub struct Options {
     pub free_safe_teleports: u16,
     pub max_safe_teleports: u16,
     pub moveable_heaps: bool,
+
+    // Fields of other releases, kept as they are
+    #[serde(flatten)]
+    pub unknown: Map<String, Value>,
+
+    // Only for the current run, never stored
+    #[serde(skip)]
+    pub seed: Option<u64>,
+    #[serde(skip)]
+    pub practice: bool,
 }
 
 impl Options {
@@ -74,51 +141,221 @@ impl Options {
         let mut options = Options {
             boardsize: BoardSize::Normal,
             boardtype: BoardType::Normal,
+            renderer: RendererType::Terminal,
 
             asciionly: false,
             colors: true,
+            theme: default_theme(),
 
             safe_moves: true,
 
             ..Default::default()
         };
 
-        options.set_profile(profiles::Profiles::Robots2);
+        options.set_profile(&profiles::Profile::get_profile(profiles::Profiles::Robots2));
 
         options
     }
 
-    pub fn load() -> Self {
+    // Where the configuration is, unless another file is chosen
+    pub fn default_path() -> PathBuf {
         let xdg_dirs = xdg::BaseDirectories::with_prefix("daleks").unwrap();
 
-        let config_path = xdg_dirs
+        xdg_dirs
             .place_config_file("config.json")
-            .expect("Cannot create configuration directory");
+            .expect("Cannot create configuration directory")
+    }
+
+    // The stored options, the defaults if there are none yet
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(serialized) => Options::parse(&serialized)
+                .map_err(|errors| format!("{}:\n  {}", path.display(), errors.join("\n  "))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Options::default()),
+            Err(e) => Err(format!("{}: {}", path.display(), e)),
+        }
+    }
 
-        if let Ok(serialized) = fs::read_to_string(config_path) {
-            if let Ok(options) = serde_json::from_str(&serialized) {
-                return options;
+    fn parse(serialized: &str) -> Result<Self, Vec<String>> {
+        let mut value: Value = serde_json::from_str(serialized).map_err(|e| vec![e.to_string()])?;
+
+        let config = value
+            .as_object_mut()
+            .ok_or(vec!["the configuration is not a JSON object".to_string()])?;
+        let version = config
+            .get("version")
+            .map_or(Some(1), Value::as_u64)
+            .ok_or(vec!["version: not a number".to_string()])?;
+
+        // when a newer release wrote it, the version stays among the unknown
+        // fields, so that it is written back as it is
+        if version <= CONFIG_VERSION {
+            config.remove("version");
+            migrate(config, version);
+        }
+
+        let options: Options = match serde_json::from_value(value.clone()) {
+            Ok(options) => options,
+            Err(e) => {
+                let mut errors = Options::field_errors(&value).unwrap_or(vec![e.to_string()]);
+                if version > CONFIG_VERSION {
+                    errors.push(format!(
+                        "written by a newer release (version {}, this one reads up to {})",
+                        version, CONFIG_VERSION
+                    ));
+                }
+                return Err(errors);
             }
+        };
+
+        let errors = options.validate();
+        if errors.is_empty() {
+            Ok(options)
+        } else {
+            Err(errors)
         }
+    }
+
+    // Which fields are wrong: each one is tried alone over the defaults
+    fn field_errors(value: &Value) -> Option<Vec<String>> {
+        let defaults = serde_json::to_value(Options::default()).unwrap();
 
-        Options::default()
+        let errors: Vec<String> = value
+            .as_object()?
+            .iter()
+            .filter_map(|(name, field)| {
+                let mut probe = defaults.clone();
+                probe[name] = field.clone();
+                serde_json::from_value::<Options>(probe)
+                    .err()
+                    .map(|e| format!("{}: {}", name, e))
+            })
+            .collect();
+
+        if errors.is_empty() {
+            None
+        } else {
+            Some(errors)
+        }
     }
 
-    pub fn store(&self) {
-        let xdg_dirs = xdg::BaseDirectories::with_prefix("daleks").unwrap();
+    // The problems of these options, one per line
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
 
-        let config_path = xdg_dirs
-            .place_config_file("config.json")
-            .expect("Cannot create configuration directory");
+        if let BoardSize::Custom(width, height) = self.boardsize {
+            if width < MIN_BOARD_SIZE.0 || height < MIN_BOARD_SIZE.1 {
+                errors.push(format!(
+                    "boardsize ({}x{}) is smaller than {}x{}",
+                    width, height, MIN_BOARD_SIZE.0, MIN_BOARD_SIZE.1
+                ));
+            }
+            if width > MAX_BOARD_SIZE.0 || height > MAX_BOARD_SIZE.1 {
+                errors.push(format!(
+                    "boardsize ({}x{}) is larger than {}x{}",
+                    width, height, MAX_BOARD_SIZE.0, MAX_BOARD_SIZE.1
+                ));
+            }
+        }
 
-        let serialized = serde_json::to_string_pretty(&self).unwrap();
+        errors.append(&mut self.keys.validate());
+        errors.append(&mut self.profile().validate());
+        errors
+    }
+
+    pub fn store(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.serialize_config()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn serialize_config(&self) -> String {
+        #[derive(Serialize)]
+        struct Config<'a> {
+            version: u64,
+            #[serde(flatten)]
+            options: &'a Options,
+        }
+
+        let mut options = self.clone();
+        let version = match options.unknown.remove("version") {
+            Some(Value::Number(version)) => version.as_u64().unwrap_or(CONFIG_VERSION),
+            _ => CONFIG_VERSION,
+        };
+
+        serde_json::to_string_pretty(&Config {
+            version,
+            options: &options,
+        })
+        .unwrap()
+    }
+
+    pub fn board_size(&self) -> (usize, usize) {
+        match self.boardsize {
+            BoardSize::Small => MIN_BOARD_SIZE,
+            BoardSize::Normal => (80, 24),
+            BoardSize::Large => (120, 36),
+            BoardSize::Huge => (160, 48),
+            BoardSize::Auto => match terminal_size() {
+                Ok((width, height)) => (
+                    (width as usize).clamp(MIN_BOARD_SIZE.0, MAX_BOARD_SIZE.0),
+                    (height as usize).clamp(MIN_BOARD_SIZE.1, MAX_BOARD_SIZE.1),
+                ),
+                // not a terminal
+                Err(_) => (80, 24),
+            },
+            BoardSize::Custom(width, height) => (width, height),
+        }
+    }
+
+    // An Auto board gets the size of the terminal now, not when it is resized
+    // or a recording is replayed somewhere else
+    pub fn fit_to_terminal(&mut self) {
+        if self.boardsize == BoardSize::Auto {
+            let (width, height) = self.board_size();
+            self.boardsize = BoardSize::Custom(width, height);
+        }
+    }
+
+    pub fn arena_size(&self) -> (usize, usize) {
+        self.arena_size_of(self.board_size())
+    }
 
-        fs::write(config_path, serialized).unwrap();
+    // The size of the arena on a normal board, the robots of the rules are for it
+    pub fn reference_arena_size(&self) -> (usize, usize) {
+        self.arena_size_of((80, 24))
     }
 
-    pub fn set_profile(&mut self, profile: profiles::Profiles) {
-        let profile = profiles::Profile::get_profile(profile);
+    fn arena_size_of(&self, (board_width, board_height): (usize, usize)) -> (usize, usize) {
+        match self.boardtype {
+            BoardType::Normal => (board_width - 2, board_height - 3),
+            BoardType::Bsd => (board_width - 2 - 19, board_height - 2),
+        }
+    }
+
+    // The rules currently in use
+    pub fn profile(&self) -> profiles::Profile {
+        profiles::Profile {
+            initial_type1: self.initial_type1,
+            initial_type2: self.initial_type2,
+            increment_type1: self.increment_type1,
+            increment_type2: self.increment_type2,
+            maximum_type1: self.maximum_type1,
+            maximum_type2: self.maximum_type2,
+            score_type1: self.score_type1,
+            score_type2: self.score_type2,
+            score_type1_waiting: self.score_type1_waiting,
+            score_type2_waiting: self.score_type2_waiting,
+            score_type1_splatted: self.score_type1_splatted,
+            score_type2_splatted: self.score_type2_splatted,
+            num_robots_per_safe: self.num_robots_per_safe,
+            safe_score_boundary: self.safe_score_boundary,
+            initial_safe_teleports: self.initial_safe_teleports,
+            free_safe_teleports: self.free_safe_teleports,
+            max_safe_teleports: self.max_safe_teleports,
+            moveable_heaps: self.moveable_heaps,
+        }
+    }
 
+    pub fn set_profile(&mut self, profile: &profiles::Profile) {
         self.initial_type1 = profile.initial_type1;
         self.initial_type2 = profile.initial_type2;
         self.increment_type1 = profile.increment_type1;
@@ -140,13 +377,125 @@ impl Options {
     }
 }
 
+// Also given to the configurations written before there were themes, see migrate()
+fn default_theme() -> String {
+    themes::Themes::Classic.to_string()
+}
+
+/*
+ * The layout of config.json, increment it with a migration below
+ * when fields are added, renamed or changed
+ */
+const CONFIG_VERSION: u64 = 4;
+
+// Brings a configuration written with an older layout to the current one
+fn migrate(config: &mut Map<String, Value>, version: u64) {
+    // 1: no version, no renderer
+    if version < 2 {
+        config
+            .entry("renderer")
+            .or_insert(serde_json::to_value(RendererType::Terminal).unwrap());
+    }
+    // 2: no theme
+    if version < 3 {
+        config.entry("theme").or_insert(default_theme().into());
+    }
+    // 3: no keys
+    if version < 4 {
+        config
+            .entry("keys")
+            .or_insert(serde_json::to_value(KeyBindings::default()).unwrap());
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
 
     #[test]
     fn base() {
-        let options = super::Options::load();
+        let dir = std::env::temp_dir().join(format!("daleks-options-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.json");
+
+        // no configuration yet
+        let mut options = Options::load(&path).unwrap();
+        options.initial_type1 = 30;
+        options.theme = "BSD".to_string();
+        options.store(&path).unwrap();
+        let stored = fs::read_to_string(&path).unwrap();
+        let loaded = Options::load(&path).unwrap();
+        assert_eq!(loaded.serialize_config(), stored);
+
+        // a configuration that cannot be loaded is left alone
+        fs::write(&path, "{\"initial_type1\": -1}").unwrap();
+        assert!(Options::load(&path).is_err());
+        let kept = fs::read_to_string(&path).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
 
-        options.store();
+        assert_eq!(loaded.initial_type1, 30);
+        assert_eq!(loaded.theme, "BSD");
+        assert_eq!(kept, "{\"initial_type1\": -1}");
+    }
+
+    #[test]
+    fn invalid() {
+        let mut options = serde_json::to_value(Options::default()).unwrap();
+        options["colors"] = "yes".into();
+        options["initial_type1"] = (-1).into();
+        let errors = Options::parse(&options.to_string()).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("colors: invalid type"));
+        assert!(errors[1].starts_with("initial_type1: invalid value"));
+
+        let mut options = Options::default();
+        options.maximum_type1 = options.initial_type1 - 1;
+        options.max_safe_teleports = 0;
+        options.boardsize = BoardSize::Custom(40000, 30000);
+        // a theme that is gone is only a warning of the board
+        options.theme = "gone".to_string();
+        let errors = Options::parse(&serde_json::to_string(&options).unwrap()).unwrap_err();
+        assert_eq!(
+            errors,
+            [
+                "boardsize (40000x30000) is larger than 1000x1000",
+                "maximum_type1 (7) is less than initial_type1 (8)",
+                "max_safe_teleports (0) is less than initial_safe_teleports (1)"
+            ]
+        );
+
+        assert!(Options::parse("{").is_err());
+    }
+
+    #[test]
+    fn migrations() {
+        // written by the first releases
+        let mut config = serde_json::to_value(Options::default()).unwrap();
+        let config = config.as_object_mut().unwrap();
+        config.remove("renderer");
+        config.remove("theme");
+        config.remove("keys");
+        let options = Options::parse(&Value::from(config.clone()).to_string()).unwrap();
+        assert_eq!(options.renderer, RendererType::Terminal);
+        assert_eq!(options.theme, "CLASSIC");
+        assert_eq!(options.keys, KeyBindings::default());
+        let stored: Value = serde_json::from_str(&options.serialize_config()).unwrap();
+        assert_eq!(stored["version"], CONFIG_VERSION);
+
+        // written by a newer release
+        config.insert("version".to_string(), (CONFIG_VERSION + 1).into());
+        config.insert("renderer".to_string(), "Text".into());
+        config.insert("theme".to_string(), "BSD".into());
+        let keys = KeyBindings {
+            quit: vec!["ESC".to_string()],
+            ..KeyBindings::default()
+        };
+        config.insert("keys".to_string(), serde_json::to_value(keys).unwrap());
+        config.insert("sound".to_string(), true.into());
+        let options = Options::parse(&Value::from(config.clone()).to_string()).unwrap();
+        assert_eq!(options.renderer, RendererType::Text);
+        let stored: Value = serde_json::from_str(&options.serialize_config()).unwrap();
+        assert_eq!(stored, Value::from(config.clone()));
     }
 }
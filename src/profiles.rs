@@ -1,4 +1,8 @@
-#[derive(Debug)]
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Profiles {
     Classic,
     Robots2,
@@ -23,6 +27,30 @@ impl std::str::FromStr for Profiles {
     }
 }
 
+impl Profiles {
+    pub const ALL: [Profiles; 5] = [
+        Profiles::Classic,
+        Profiles::Robots2,
+        Profiles::Nightmare,
+        Profiles::Robots2Easy,
+        Profiles::ClassicWithSafeTeleports,
+    ];
+}
+
+impl std::fmt::Display for Profiles {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Profiles::Classic => "CLASSIC",
+            Profiles::Robots2 => "ROBOTS2",
+            Profiles::Nightmare => "NIGHTMARE",
+            Profiles::Robots2Easy => "ROBOTS2EASY",
+            Profiles::ClassicWithSafeTeleports => "CLASSICWITHSAFETELEPORTS",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Profile {
     pub initial_type1: u16,
     pub initial_type2: u16,
@@ -132,6 +160,47 @@ impl Profile {
         profile
     }
 
+    // The name of the built-in or user profile with exactly these rules, if any
+    pub fn name(&self) -> String {
+        for (name, profile) in all() {
+            if profile == *self {
+                return name;
+            }
+        }
+        "CUSTOM".to_string()
+    }
+
+    // The problems of these rules, one per line
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if self.maximum_type1 < self.initial_type1 {
+            errors.push(format!(
+                "maximum_type1 ({}) is less than initial_type1 ({})",
+                self.maximum_type1, self.initial_type1
+            ));
+        }
+        if self.maximum_type2 < self.initial_type2 {
+            errors.push(format!(
+                "maximum_type2 ({}) is less than initial_type2 ({})",
+                self.maximum_type2, self.initial_type2
+            ));
+        }
+        if self.initial_type1 == 0 && self.initial_type2 == 0 {
+            errors.push(
+                "initial_type1 and initial_type2 are both 0, there are no robots".to_string(),
+            );
+        }
+        if self.max_safe_teleports < self.initial_safe_teleports {
+            errors.push(format!(
+                "max_safe_teleports ({}) is less than initial_safe_teleports ({})",
+                self.max_safe_teleports, self.initial_safe_teleports
+            ));
+        }
+
+        errors
+    }
+
     pub fn get_profile(profile: Profiles) -> Self {
         match profile {
             Profiles::Classic => Profile::classic(),
@@ -142,3 +211,165 @@ impl Profile {
         }
     }
 }
+
+/*
+ * User profiles are JSON files with the fields of Profile in the profiles
+ * config directory (~/.config/daleks/profiles), the file name is the profile name
+ */
+pub struct UserProfile {
+    pub name: String,
+    pub rules: Result<Profile, String>,
+}
+
+pub fn user_profiles() -> Vec<UserProfile> {
+    let xdg_dirs = xdg::BaseDirectories::with_prefix("daleks").unwrap();
+
+    load_user_profiles(xdg_dirs.list_config_files_once("profiles"))
+}
+
+fn load_user_profiles(paths: Vec<PathBuf>) -> Vec<UserProfile> {
+    let mut profiles: Vec<UserProfile> = paths
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_str()?.to_string();
+            let rules = load_user_profile(&path);
+            Some(UserProfile { name, rules })
+        })
+        .collect();
+
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
+    profiles
+}
+
+fn load_user_profile(path: &Path) -> Result<Profile, String> {
+    let serialized = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    let profile: Profile =
+        serde_json::from_str(&serialized).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    let errors = profile.validate();
+    if !errors.is_empty() {
+        return Err(format!("{}: {}", path.display(), errors.join(", ")));
+    }
+
+    Ok(profile)
+}
+
+// The built-in profiles followed by the valid user profiles
+pub fn all() -> Vec<(String, Profile)> {
+    let mut profiles: Vec<(String, Profile)> = Profiles::ALL
+        .iter()
+        .map(|&profile| (profile.to_string(), Profile::get_profile(profile)))
+        .collect();
+
+    for user_profile in user_profiles() {
+        if let Ok(rules) = user_profile.rules {
+            profiles.push((user_profile.name, rules));
+        }
+    }
+
+    profiles
+}
+
+// A built-in profile or else a user profile, by name (ignoring case)
+pub fn find(name: &str) -> Result<Profile, String> {
+    if let Ok(profile) = name.parse::<Profiles>() {
+        return Ok(Profile::get_profile(profile));
+    }
+
+    match user_profiles()
+        .into_iter()
+        .find(|user_profile| user_profile.name.eq_ignore_ascii_case(name))
+    {
+        Some(user_profile) => user_profile.rules,
+        None => Err(format!(
+            "'{}' is not a built-in or user profile (see --list-profiles)",
+            name
+        )),
+    }
+}
+
+pub fn format_list() -> String {
+    let mut text = format!(
+        "{:<28}{:>8}{:>8}{:>6}{:>6}{:>6}{:>7}  {}\n",
+        "PROFILE", "ROBOT1", "ROBOT2", "SAFE", "MAX", "FREE", "KILLS", "HEAPS"
+    );
+
+    let mut errors = String::new();
+
+    let builtin = Profiles::ALL
+        .iter()
+        .map(|&profile| (profile.to_string(), Ok(Profile::get_profile(profile))));
+    let user = user_profiles()
+        .into_iter()
+        .map(|user_profile| (format!("{} (user)", user_profile.name), user_profile.rules));
+
+    for (name, rules) in builtin.chain(user) {
+        match rules {
+            Ok(rules) => text.push_str(&format!(
+                "{:<28}{:>8}{:>8}{:>6}{:>6}{:>6}{:>7}  {}\n",
+                name,
+                format!("{}+{}", rules.initial_type1, rules.increment_type1),
+                format!("{}+{}", rules.initial_type2, rules.increment_type2),
+                rules.initial_safe_teleports,
+                rules.max_safe_teleports,
+                rules.free_safe_teleports,
+                rules.num_robots_per_safe,
+                if rules.moveable_heaps {
+                    "moveable"
+                } else {
+                    "fixed"
+                }
+            )),
+            Err(e) => errors.push_str(&format!("ERROR: {}\n", e)),
+        }
+    }
+
+    text.push_str(
+        "\nROBOT1, ROBOT2: robots on the first level + more on every level\n\
+         SAFE, MAX, FREE: safe teleports at start, at most, free on every level\n\
+         KILLS: robots killed while waiting for a safe teleport\n",
+    );
+
+    text + &errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_profiles() {
+        let dir = std::env::temp_dir().join(format!("daleks-profiles-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut marathon = Profile::get_profile(Profiles::Classic);
+        marathon.maximum_type1 = 20;
+        fs::write(
+            dir.join("marathon.json"),
+            serde_json::to_string(&marathon).unwrap(),
+        )
+        .unwrap();
+        fs::write(dir.join("broken.json"), "{\"initial_type1\": 1}").unwrap();
+        fs::write(dir.join("notes.txt"), "not a profile").unwrap();
+
+        let paths = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        let profiles = load_user_profiles(paths);
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(profiles.len(), 2);
+        assert_eq!(profiles[0].name, "broken");
+        assert!(profiles[0]
+            .rules
+            .as_ref()
+            .unwrap_err()
+            .contains("missing field"));
+        assert_eq!(profiles[1].name, "marathon");
+        assert_eq!(profiles[1].rules, Ok(marathon));
+    }
+}
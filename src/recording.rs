@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::enums::PlayerCommand;
+use crate::options::Options;
+
+/*
+ * Everything needed to play again exactly the same game:
+ * the seed, the rules and the ordered list of commands
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Recording {
+    pub seed: u64,
+    pub options: Options,
+    pub commands: Vec<PlayerCommand>,
+}
+
+impl Recording {
+    pub fn new(options: &Options, seed: u64) -> Self {
+        Recording {
+            seed,
+            options: options.clone(),
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, command: PlayerCommand) {
+        self.commands.push(command);
+    }
+
+    /*
+     * Every game is recorded in the XDG data directory
+     * (e.g. ~/.local/share/daleks/recordings/game-<seed>.json), another game
+     * with the same seed gets a counter (game-<seed>-2.json)
+     */
+    pub fn default_path(seed: u64) -> PathBuf {
+        let xdg_dirs = xdg::BaseDirectories::with_prefix("daleks").unwrap();
+
+        let dir = xdg_dirs
+            .create_data_directory("recordings")
+            .expect("Cannot create recordings directory");
+        free_path(&dir, seed)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let serialized = fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read recording '{}': {}", path, e))?;
+
+        let recording: Recording = serde_json::from_str(&serialized)
+            .map_err(|e| format!("'{}' is not a valid recording: {}", path, e))?;
+
+        // the engine trusts the options, e.g. the board size
+        let errors = recording.options.validate();
+        if errors.is_empty() {
+            Ok(recording)
+        } else {
+            Err(format!(
+                "'{}' is not a valid recording: {}",
+                path,
+                errors.join(", ")
+            ))
+        }
+    }
+
+    pub fn store(&self) {
+        let serialized = serde_json::to_string(&self).unwrap();
+
+        fs::write(Recording::default_path(self.seed), serialized).unwrap();
+    }
+}
+
+// The first name not taken by an earlier recording
+fn free_path(dir: &Path, seed: u64) -> PathBuf {
+    (1..)
+        .map(|n| match n {
+            1 => dir.join(format!("game-{}.json", seed)),
+            n => dir.join(format!("game-{}-{}.json", seed, n)),
+        })
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+    use crate::enums::GameState;
+
+    #[test]
+    fn replay() {
+        let options = Options::default();
+
+        let mut recording = Recording::new(&options, 7);
+        let mut engine = Engine::new(&options);
+        engine.new_game(7);
+        for command in [
+            PlayerCommand::RandomTeleport,
+            PlayerCommand::N,
+            PlayerCommand::SafeTeleport,
+            PlayerCommand::RandomTeleport,
+        ] {
+            recording.push(command);
+            engine.player_command(command);
+        }
+
+        let serialized = serde_json::to_string(&recording).unwrap();
+        let recording: Recording = serde_json::from_str(&serialized).unwrap();
+
+        let mut replayed = Engine::new(&recording.options);
+        replayed.new_game(recording.seed);
+        for &command in &recording.commands {
+            replayed.player_command(command);
+        }
+
+        assert!(replayed.arena() == engine.arena());
+        assert_eq!(replayed.score(), engine.score());
+        assert_ne!(replayed.state(), GameState::Waiting);
+    }
+
+    #[test]
+    fn same_seed() {
+        let dir = std::env::temp_dir().join(format!("daleks-recordings-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let first = free_path(&dir, 7);
+        fs::write(&first, "").unwrap();
+        let second = free_path(&dir, 7);
+        fs::write(&second, "").unwrap();
+        let third = free_path(&dir, 7);
+        let other = free_path(&dir, 8);
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(first, dir.join("game-7.json"));
+        assert_eq!(second, dir.join("game-7-2.json"));
+        assert_eq!(third, dir.join("game-7-3.json"));
+        assert_eq!(other, dir.join("game-8.json"));
+    }
+}
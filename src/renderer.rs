@@ -0,0 +1,51 @@
+use crate::accessibleboard::AccessibleBoard;
+use crate::board::Board;
+use crate::enums::{ObjectType, PlayerCommand};
+use crate::options::{Options, RendererType};
+use crate::textboard::TextBoard;
+
+/*
+ * What the game needs from a front-end
+ */
+pub trait Renderer {
+    fn init(&mut self) -> Result<(), String>;
+
+    fn close(&mut self);
+
+    fn set_status(&mut self, level: u16, score: u64, safeteleports: u16, seed: u64);
+
+    // Whether the safe moves are told, they take time to find
+    fn wants_safe_moves(&self) -> bool;
+
+    // The moves that don't kill the player, told before the next update
+    fn set_safe_moves(&mut self, safe_moves: &[PlayerCommand]);
+
+    fn update(&mut self, arena: &[ObjectType]);
+
+    // A short message near the player (e.g. on death)
+    fn show_player_message(&mut self, x: usize, y: usize, message: &str);
+
+    // A centered message or question
+    fn show_message(&mut self, message: &str);
+
+    // A longer text (e.g. the help)
+    fn show_dialog(&mut self, message: &str);
+
+    // Tells everything on the board (for those who can't see it)
+    fn describe(&mut self);
+
+    // Shows another part of the arena when it does not fit in the terminal
+    fn pan(&mut self, dx: i16, dy: i16);
+
+    // Draws everything again after the terminal has been resized,
+    // false if the terminal is now too small for the game
+    fn resize(&mut self) -> bool;
+}
+
+pub fn new_renderer(options: &Options) -> Result<Box<dyn Renderer>, String> {
+    Ok(match options.renderer {
+        RendererType::Terminal => Box::new(Board::new(options)?),
+        RendererType::Text => Box::new(TextBoard::new(options)),
+        RendererType::Accessible => Box::new(AccessibleBoard::new(options)),
+    })
+}
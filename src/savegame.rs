@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::engine::Engine;
+use crate::options::Options;
+use crate::recording::Recording;
+
+/*
+ * A game interrupted with 'q', to be resumed on the next launch
+ */
+#[derive(Serialize, Deserialize)]
+pub struct SavedGame {
+    pub options: Options,
+    pub engine: Engine,
+    pub recording: Option<Recording>,
+}
+
+impl SavedGame {
+    fn path() -> PathBuf {
+        let xdg_dirs = xdg::BaseDirectories::with_prefix("daleks").unwrap();
+
+        xdg_dirs
+            .place_data_file("savegame.json")
+            .expect("Cannot create data directory")
+    }
+
+    // None when there is no saved game
+    pub fn load() -> Result<Option<Self>, String> {
+        let path = SavedGame::path();
+        let serialized = match fs::read_to_string(&path) {
+            Ok(serialized) => serialized,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+
+        let saved: SavedGame =
+            serde_json::from_str(&serialized).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        // the engine trusts the options and indexes its arena with them
+        let mut errors = saved.options.validate();
+        if errors.is_empty() {
+            errors = saved.engine.validate(&saved.options);
+        }
+        if errors.is_empty() {
+            Ok(Some(saved))
+        } else {
+            Err(format!("{}: {}", path.display(), errors.join(", ")))
+        }
+    }
+
+    pub fn store(&self) {
+        let serialized = serde_json::to_string(&self).unwrap();
+
+        fs::write(SavedGame::path(), serialized).unwrap();
+    }
+
+    pub fn remove() {
+        let _ = fs::remove_file(SavedGame::path());
+    }
+}
@@ -0,0 +1,261 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::profiles::Profile;
+
+// Scores kept for each set of rules
+const MAX_SCORES: usize = 10;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Score {
+    pub name: String,
+    pub score: u64,
+    pub level: u16,
+    pub date: String,
+    pub profile: String,
+    // scores are ranked only against games played with the same rules,
+    // on a board of the same size (the robots grow with it)
+    pub rules: Profile,
+    pub board_size: (usize, usize),
+}
+
+impl Score {
+    fn ranked_with(&self, rules: &Profile, board_size: (usize, usize)) -> bool {
+        self.rules == *rules && self.board_size == board_size
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct HighScores {
+    scores: Vec<Score>,
+}
+
+impl HighScores {
+    fn path() -> PathBuf {
+        let xdg_dirs = xdg::BaseDirectories::with_prefix("daleks").unwrap();
+
+        xdg_dirs
+            .place_data_file("scores.json")
+            .expect("Cannot create data directory")
+    }
+
+    // The scores so far, an error when the file cannot be read (it must not be overwritten)
+    pub fn load() -> Result<Self, String> {
+        let path = HighScores::path();
+        match fs::read_to_string(&path) {
+            Ok(serialized) => {
+                serde_json::from_str(&serialized).map_err(|e| format!("{}: {}", path.display(), e))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(HighScores::default()),
+            Err(e) => Err(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn store(&self) {
+        let serialized = serde_json::to_string_pretty(&self).unwrap();
+
+        fs::write(HighScores::path(), serialized).unwrap();
+    }
+
+    /*
+     * Add a score for the current player and return its position in the table
+     * (None if it's not good enough to enter it)
+     */
+    pub fn add(
+        &mut self,
+        score: u64,
+        level: u16,
+        rules: &Profile,
+        board_size: (usize, usize),
+    ) -> Option<usize> {
+        let entry = Score {
+            name: player_name(),
+            score,
+            level,
+            date: today(),
+            profile: rules.name(),
+            rules: rules.clone(),
+            board_size,
+        };
+
+        let table = self.table(rules, board_size);
+        let position = table
+            .iter()
+            .position(|s| s.score < score)
+            .unwrap_or(table.len());
+
+        if score == 0 || position >= MAX_SCORES {
+            return None;
+        }
+
+        // insert after every better or equal score, then drop the excess
+        let index = self
+            .scores
+            .iter()
+            .position(|s| s.ranked_with(rules, board_size) && s.score < score)
+            .unwrap_or(self.scores.len());
+        self.scores.insert(index, entry);
+
+        let mut count = 0;
+        self.scores.retain(|s| {
+            if s.ranked_with(rules, board_size) {
+                count += 1;
+                count <= MAX_SCORES
+            } else {
+                true
+            }
+        });
+
+        Some(position)
+    }
+
+    // The best scores obtained with these rules on a board of this size
+    pub fn table(&self, rules: &Profile, board_size: (usize, usize)) -> Vec<&Score> {
+        let mut table: Vec<&Score> = self
+            .scores
+            .iter()
+            .filter(|s| s.ranked_with(rules, board_size))
+            .collect();
+        table.sort_by_key(|s| Reverse(s.score));
+        table.truncate(MAX_SCORES);
+        table
+    }
+
+    pub fn format_table(
+        &self,
+        rules: &Profile,
+        board_size: (usize, usize),
+        highlight: Option<usize>,
+    ) -> String {
+        let mut text = format!(
+            " High scores - {} {}x{}\n\n",
+            rules.name(),
+            board_size.0,
+            board_size.1
+        );
+        text += "     Name            Score  Level  Date       \n";
+
+        let table = self.table(rules, board_size);
+        if table.is_empty() {
+            text += "     (no scores yet)\n";
+        }
+
+        for (i, score) in table.iter().enumerate() {
+            let mark = if highlight == Some(i) { '>' } else { ' ' };
+            let name: String = score.name.chars().take(14).collect();
+            text += &format!(
+                "{}{:>3} {:<14} {:>6} {:>6}  {}\n",
+                mark,
+                i + 1,
+                name,
+                score.score,
+                score.level,
+                score.date
+            );
+        }
+
+        text
+    }
+
+    // Every table, one for each set of rules and board size
+    pub fn format_all(&self) -> String {
+        let mut tables_list: Vec<(&Profile, (usize, usize))> = Vec::new();
+        for score in &self.scores {
+            if !tables_list.contains(&(&score.rules, score.board_size)) {
+                tables_list.push((&score.rules, score.board_size));
+            }
+        }
+
+        if tables_list.is_empty() {
+            return "No high scores yet.\n".to_string();
+        }
+
+        let tables: Vec<String> = tables_list
+            .iter()
+            .map(|(rules, board_size)| self.format_table(rules, *board_size, None))
+            .collect();
+        tables.join("\n")
+    }
+}
+
+// The login name: there is no question to answer at the end of a game
+fn player_name() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("LOGNAME"))
+        .unwrap_or_else(|_| "player".to_string())
+}
+
+fn today() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// Days since 1970-01-01 to (year, month, day), see http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profiles::{Profile, Profiles};
+
+    #[test]
+    fn ranked_by_rules() {
+        const NORMAL: (usize, usize) = (80, 24);
+        let classic = Profile::get_profile(Profiles::Classic);
+        let nightmare = Profile::get_profile(Profiles::Nightmare);
+
+        let mut highscores = HighScores::default();
+        assert_eq!(highscores.add(100, 2, &classic, NORMAL), Some(0));
+        assert_eq!(highscores.add(50, 1, &nightmare, NORMAL), Some(0));
+        assert_eq!(highscores.add(200, 3, &classic, NORMAL), Some(0));
+        assert_eq!(highscores.add(70, 1, &nightmare, NORMAL), Some(0));
+        assert_eq!(highscores.add(0, 1, &nightmare, NORMAL), None);
+
+        let table = highscores.table(&classic, NORMAL);
+        assert_eq!(table.len(), 2);
+        assert_eq!(table[0].score, 200);
+        assert_eq!(table[0].profile, "CLASSIC");
+
+        for score in 1..=MAX_SCORES as u64 {
+            highscores.add(score * 1000, 1, &nightmare, NORMAL);
+        }
+        assert_eq!(highscores.add(10, 1, &nightmare, NORMAL), None);
+        assert_eq!(highscores.table(&nightmare, NORMAL).len(), MAX_SCORES);
+        assert_eq!(highscores.table(&classic, NORMAL).len(), 2);
+
+        // a huge board has more robots, its scores are apart
+        assert_eq!(highscores.add(10, 1, &classic, (160, 48)), Some(0));
+        assert_eq!(highscores.table(&classic, (160, 48)).len(), 1);
+        assert_eq!(highscores.table(&classic, NORMAL).len(), 2);
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19000), (2022, 1, 8));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+    }
+}
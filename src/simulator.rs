@@ -0,0 +1,202 @@
+use crate::ai::{self, Strategy};
+use crate::engine::Engine;
+use crate::enums::*;
+use crate::options::Options;
+use crate::profiles::{self, Profile};
+
+/*
+ * Plays many games without a terminal, to see how hard a profile really is
+ */
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub games: u32,
+    pub won: u32,
+    // games that started / completed each level
+    pub started: Vec<u32>,
+    pub completed: Vec<u32>,
+    pub total_score: u64,
+    pub max_score: u64,
+    pub safe_teleports_earned: u32,
+    pub safe_teleports_spent: u32,
+    pub random_teleports: u32,
+    pub random_teleport_deaths: u32,
+}
+
+impl Stats {
+    pub fn format(&self) -> String {
+        let mut s = String::new();
+
+        if self.games == 0 {
+            return s;
+        }
+
+        s.push_str(&format!(
+            "  Games won: {} ({:.1}%)\n",
+            self.won,
+            percent(self.won, self.games)
+        ));
+        s.push_str(&format!(
+            "  Score: mean {:.1}, max {}\n",
+            self.total_score as f64 / self.games as f64,
+            self.max_score
+        ));
+        s.push_str(&format!(
+            "  Safe teleports per game: {:.2} earned, {:.2} spent\n",
+            self.safe_teleports_earned as f64 / self.games as f64,
+            self.safe_teleports_spent as f64 / self.games as f64
+        ));
+        s.push_str(&format!(
+            "  Random teleports: {} used, {} killed you ({:.1}%)\n",
+            self.random_teleports,
+            self.random_teleport_deaths,
+            percent(self.random_teleport_deaths, self.random_teleports)
+        ));
+
+        s.push_str("  Level  Games  Survived\n");
+        for (level, (started, completed)) in self.started.iter().zip(&self.completed).enumerate() {
+            s.push_str(&format!(
+                "  {:>5}  {:>5}  {:>7.1}%\n",
+                level + 1,
+                started,
+                percent(*completed, *started)
+            ));
+        }
+
+        s
+    }
+
+    fn start_level(&mut self, level: u16) {
+        let level = level as usize;
+        if self.started.len() <= level {
+            self.started.resize(level + 1, 0);
+            self.completed.resize(level + 1, 0);
+        }
+        self.started[level] += 1;
+    }
+}
+
+fn percent(part: u32, total: u32) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
+
+/*
+ * Plays the games with every profile (or only the given one) and prints the stats
+ */
+pub fn run(options: &Options, games: u32, strategy: Strategy, profile: Option<Profile>) {
+    let first_seed = options.seed.unwrap_or(1);
+
+    let profiles = match profile {
+        Some(profile) => vec![(profile.name(), profile)],
+        None => profiles::all(),
+    };
+
+    for (name, profile) in profiles {
+        let mut options = options.clone();
+        options.set_profile(&profile);
+
+        let stats = simulate(&options, first_seed, games, strategy);
+
+        println!(
+            "{} ({} games, seeds {}-{}, {} strategy)",
+            name,
+            games,
+            first_seed,
+            first_seed + games.saturating_sub(1) as u64,
+            strategy
+        );
+        println!("{}", stats.format());
+    }
+}
+
+pub fn simulate(options: &Options, first_seed: u64, games: u32, strategy: Strategy) -> Stats {
+    let mut stats = Stats::default();
+
+    for i in 0..games {
+        let mut engine = Engine::new(options);
+        engine.new_game(first_seed + i as u64);
+
+        play(&mut engine, strategy, &mut stats);
+    }
+
+    stats
+}
+
+fn play(engine: &mut Engine, strategy: Strategy, stats: &mut Stats) {
+    stats.games += 1;
+    stats.start_level(engine.level());
+
+    while engine.state() == GameState::Playing {
+        let before = engine.safeteleports();
+
+        let command = ai::choose(engine, strategy);
+        let outcome = engine.player_command(command);
+
+        if engine.state() == GameState::Waiting {
+            engine.finish_waiting();
+        }
+
+        let mut spent = 0;
+        if outcome.accepted {
+            match command {
+                PlayerCommand::SafeTeleport => spent = 1,
+                PlayerCommand::RandomTeleport => {
+                    stats.random_teleports += 1;
+                    if engine.state() == GameState::Dead {
+                        stats.random_teleport_deaths += 1;
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        if engine.state() == GameState::LevelComplete {
+            stats.completed[engine.level() as usize] += 1;
+            engine.next_level();
+            if engine.state() == GameState::Playing {
+                stats.start_level(engine.level());
+            }
+        }
+
+        stats.safe_teleports_spent += spent;
+        stats.safe_teleports_earned +=
+            (engine.safeteleports() + spent as u16).saturating_sub(before) as u32;
+    }
+
+    if engine.state() == GameState::GameComplete {
+        stats.won += 1;
+    }
+    stats.total_score += engine.score();
+    stats.max_score = stats.max_score.max(engine.score());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simulate() {
+        let mut options = Options::default();
+        options.set_profile(&Profile::get_profile(profiles::Profiles::Classic));
+
+        let stats = super::simulate(&options, 1, 3, Strategy::Greedy);
+
+        assert_eq!(stats.games, 3);
+        assert_eq!(stats.started[0], 3);
+        assert!(stats
+            .started
+            .iter()
+            .zip(&stats.completed)
+            .all(|(s, c)| c <= s));
+        assert!(stats.max_score * 3 >= stats.total_score);
+        // no safe teleports in the classic rules
+        assert_eq!(stats.safe_teleports_earned, 0);
+
+        // the same seeds give the same games
+        assert_eq!(stats, super::simulate(&options, 1, 3, Strategy::Greedy));
+    }
+}
@@ -0,0 +1,146 @@
+use std::io::{stdout, Write};
+
+use crate::enums::{ObjectType, PlayerCommand};
+use crate::keys;
+use crate::options::Options;
+use crate::renderer::Renderer;
+
+/*
+ * A renderer printing plain lines, for pipes and dumb terminals
+ */
+pub struct TextBoard {
+    arena_width: usize,
+    arena_height: usize,
+
+    level: u16,
+    score: u64,
+    safeteleports: u16,
+    seed: u64,
+
+    help_key: String,
+}
+
+impl TextBoard {
+    pub fn new(options: &Options) -> Self {
+        let (arena_width, arena_height) = options.arena_size();
+
+        TextBoard {
+            arena_width,
+            arena_height,
+
+            level: 0,
+            score: 0,
+            safeteleports: 0,
+            seed: 0,
+
+            help_key: keys::main_key(&options.keys.help),
+        }
+    }
+
+    fn print(&self, text: &str) {
+        let mut stdout = stdout();
+        for line in text.lines() {
+            writeln!(stdout, "{}", line).unwrap();
+        }
+        stdout.flush().unwrap();
+    }
+}
+
+impl Renderer for TextBoard {
+    fn init(&mut self) -> Result<(), String> {
+        self.print(&format!(
+            "Escape from evil robots who want to exterminate you. Type {} for help.",
+            self.help_key
+        ));
+        Ok(())
+    }
+
+    fn close(&mut self) {}
+
+    fn set_status(&mut self, level: u16, score: u64, safeteleports: u16, seed: u64) {
+        self.level = level + 1;
+        self.score = score;
+        self.safeteleports = safeteleports;
+        self.seed = seed;
+    }
+
+    fn wants_safe_moves(&self) -> bool {
+        false
+    }
+
+    fn set_safe_moves(&mut self, _safe_moves: &[PlayerCommand]) {}
+
+    fn update(&mut self, arena: &[ObjectType]) {
+        let mut text = format_arena(arena, self.arena_width, self.arena_height);
+        text += &format!(
+            "Level: {}  Score: {}  Seed: {}  Safe teleports: {}\n",
+            self.level, self.score, self.seed, self.safeteleports
+        );
+        self.print(&text);
+    }
+
+    fn show_player_message(&mut self, _x: usize, _y: usize, message: &str) {
+        self.print(message);
+    }
+
+    fn show_message(&mut self, message: &str) {
+        self.print(message);
+    }
+
+    fn show_dialog(&mut self, message: &str) {
+        self.print(message);
+        self.print("(press ENTER)");
+    }
+
+    // the whole arena is printed at every update
+    fn describe(&mut self) {}
+
+    // the whole arena is always printed
+    fn pan(&mut self, _dx: i16, _dy: i16) {}
+
+    // lines scroll, nothing to redraw
+    fn resize(&mut self) -> bool {
+        true
+    }
+}
+
+fn format_arena(arena: &[ObjectType], arena_width: usize, arena_height: usize) -> String {
+    let wall = format!("+{}+\n", "-".repeat(arena_width));
+
+    let mut text = wall.clone();
+    for y in 0..arena_height {
+        text.push('|');
+        for x in 0..arena_width {
+            text.push(match arena[x + y * arena_width] {
+                ObjectType::None => ' ',
+                ObjectType::Player => '@',
+                ObjectType::Heap => '*',
+                ObjectType::Robot1 => '+',
+                ObjectType::Robot2 => '#',
+            });
+        }
+        text += "|\n";
+    }
+    text += &wall;
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arena() {
+        let arena = [
+            ObjectType::Player,
+            ObjectType::None,
+            ObjectType::Robot1,
+            ObjectType::Heap,
+            ObjectType::Robot2,
+            ObjectType::None,
+        ];
+
+        assert_eq!(format_arena(&arena, 3, 2), "+---+\n|@ +|\n|*# |\n+---+\n");
+    }
+}
@@ -0,0 +1,652 @@
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use termion::color;
+use termion::style;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Themes {
+    Classic,
+    Bsd,
+    HighContrast,
+    Emoji,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+    Monochrome,
+}
+
+impl std::str::FromStr for Themes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let p = s.to_uppercase();
+        match p.as_str() {
+            "CLASSIC" => Ok(Themes::Classic),
+            "BSD" => Ok(Themes::Bsd),
+            "HIGHCONTRAST" => Ok(Themes::HighContrast),
+            "EMOJI" => Ok(Themes::Emoji),
+            "DEUTERANOPIA" => Ok(Themes::Deuteranopia),
+            "PROTANOPIA" => Ok(Themes::Protanopia),
+            "TRITANOPIA" => Ok(Themes::Tritanopia),
+            "MONOCHROME" => Ok(Themes::Monochrome),
+            _ => Err(format!("'{}' is not a valid value for Themes", s)),
+        }
+    }
+}
+
+impl Themes {
+    pub const ALL: [Themes; 8] = [
+        Themes::Classic,
+        Themes::Bsd,
+        Themes::HighContrast,
+        Themes::Emoji,
+        Themes::Deuteranopia,
+        Themes::Protanopia,
+        Themes::Tritanopia,
+        Themes::Monochrome,
+    ];
+}
+
+impl std::fmt::Display for Themes {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Themes::Classic => "CLASSIC",
+            Themes::Bsd => "BSD",
+            Themes::HighContrast => "HIGHCONTRAST",
+            Themes::Emoji => "EMOJI",
+            Themes::Deuteranopia => "DEUTERANOPIA",
+            Themes::Protanopia => "PROTANOPIA",
+            Themes::Tritanopia => "TRITANOPIA",
+            Themes::Monochrome => "MONOCHROME",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// The 16 colors every color terminal has, Default is the color of the terminal
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnsiColor {
+    Default,
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    LightBlack,
+    LightRed,
+    LightGreen,
+    LightYellow,
+    LightBlue,
+    LightMagenta,
+    LightCyan,
+    LightWhite,
+}
+
+// How many colors the terminal shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorDepth {
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+impl ColorDepth {
+    // From the environment, as there is no way to ask the terminal
+    pub fn detect() -> Self {
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            ColorDepth::TrueColor
+        } else if term.contains("256color") {
+            ColorDepth::Ansi256
+        } else {
+            ColorDepth::Ansi16
+        }
+    }
+}
+
+/*
+ * A color for every depth: the 16 colors are mandatory,
+ * the others are used when the terminal has them
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    pub ansi: AnsiColor,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ansi256: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rgb: Option<(u8, u8, u8)>,
+}
+
+impl Color {
+    const fn new(ansi: AnsiColor, ansi256: u8, rgb: (u8, u8, u8)) -> Self {
+        Color {
+            ansi,
+            ansi256: Some(ansi256),
+            rgb: Some(rgb),
+        }
+    }
+
+    const fn ansi(ansi: AnsiColor) -> Self {
+        Color {
+            ansi,
+            ansi256: None,
+            rgb: None,
+        }
+    }
+
+    // The escape sequence of the foreground color
+    pub fn fg(&self, depth: ColorDepth) -> String {
+        match (self.rgb, self.ansi256) {
+            (Some((r, g, b)), _) if depth == ColorDepth::TrueColor => {
+                color::Fg(color::Rgb(r, g, b)).to_string()
+            }
+            (_, Some(value)) if depth >= ColorDepth::Ansi256 => {
+                color::Fg(color::AnsiValue(value)).to_string()
+            }
+            _ => match self.ansi_value() {
+                Some(value) => color::Fg(color::AnsiValue(value)).to_string(),
+                None => color::Fg(color::Reset).to_string(),
+            },
+        }
+    }
+
+    // The escape sequence of the background color
+    pub fn bg(&self, depth: ColorDepth) -> String {
+        match (self.rgb, self.ansi256) {
+            (Some((r, g, b)), _) if depth == ColorDepth::TrueColor => {
+                color::Bg(color::Rgb(r, g, b)).to_string()
+            }
+            (_, Some(value)) if depth >= ColorDepth::Ansi256 => {
+                color::Bg(color::AnsiValue(value)).to_string()
+            }
+            _ => match self.ansi_value() {
+                Some(value) => color::Bg(color::AnsiValue(value)).to_string(),
+                None => color::Bg(color::Reset).to_string(),
+            },
+        }
+    }
+
+    fn ansi_value(&self) -> Option<u8> {
+        match self.ansi {
+            AnsiColor::Default => None,
+            ansi => Some(ansi as u8 - 1),
+        }
+    }
+}
+
+// Shown also without colors
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Bold,
+    Reverse,
+    Underline,
+}
+
+impl Style {
+    pub fn escape(&self) -> String {
+        match self {
+            Style::Bold => style::Bold.to_string(),
+            Style::Reverse => style::Invert.to_string(),
+            Style::Underline => style::Underline.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Tile {
+    pub glyph: String,
+    pub color: Color,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub style: Vec<Style>,
+}
+
+impl Tile {
+    fn new(glyph: &str, color: Color) -> Self {
+        Tile {
+            glyph: glyph.to_string(),
+            color,
+            style: Vec::new(),
+        }
+    }
+
+    fn styled(mut self, style: &[Style]) -> Self {
+        self.style = style.to_vec();
+        self
+    }
+}
+
+/*
+ * The look of the board: the glyph and the color of everything on it
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    // every cell of the arena is two columns wide, for glyphs like emoji:
+    // a glyph is then a wide character or two normal ones
+    #[serde(default)]
+    pub wide: bool,
+    pub player: Tile,
+    pub robot1: Tile,
+    pub robot2: Tile,
+    pub heap: Tile,
+    pub empty: Tile,
+    // the corners (top left, top right, bottom left, bottom right),
+    // the horizontal and the vertical wall
+    pub wall: Tile,
+    pub text: Color,
+    // the color of the terminal when missing
+    #[serde(default)]
+    pub background: Option<Color>,
+}
+
+const WHITE: Color = Color::new(AnsiColor::White, 250, (208, 208, 208));
+const BRIGHT_WHITE: Color = Color::new(AnsiColor::LightWhite, 231, (255, 255, 255));
+const BLACK: Color = Color::new(AnsiColor::Black, 16, (0, 0, 0));
+
+impl Theme {
+    // The look daleks always had
+    fn classic() -> Self {
+        Theme {
+            wide: false,
+            player: Tile::new("@", Color::new(AnsiColor::Green, 40, (0, 215, 0))),
+            robot1: Tile::new("+", BRIGHT_WHITE),
+            robot2: Tile::new("#", Color::new(AnsiColor::LightRed, 203, (255, 95, 95))),
+            heap: Tile::new("*", Color::new(AnsiColor::Yellow, 178, (215, 175, 0))),
+            empty: Tile::new(" ", WHITE),
+            wall: Tile::new("╔╗╚╝═║", Color::new(AnsiColor::Blue, 33, (0, 135, 255))),
+            text: WHITE,
+            background: Some(BLACK),
+        }
+    }
+
+    // Like robots on BSD: plain characters in the colors of the terminal
+    fn bsd() -> Self {
+        let plain = Color::ansi(AnsiColor::Default);
+        Theme {
+            wide: false,
+            player: Tile::new("@", plain),
+            robot1: Tile::new("+", plain),
+            robot2: Tile::new("#", plain),
+            heap: Tile::new("*", plain),
+            empty: Tile::new(" ", plain),
+            wall: Tile::new("++++-|", plain),
+            text: plain,
+            background: None,
+        }
+    }
+
+    // Only the brightest colors on black
+    fn high_contrast() -> Self {
+        Theme {
+            wide: false,
+            player: Tile::new("@", Color::new(AnsiColor::LightYellow, 226, (255, 255, 0))),
+            robot1: Tile::new("+", BRIGHT_WHITE),
+            robot2: Tile::new("#", Color::new(AnsiColor::LightRed, 196, (255, 0, 0))),
+            heap: Tile::new("*", Color::new(AnsiColor::LightCyan, 51, (0, 255, 255))),
+            empty: Tile::new(" ", BRIGHT_WHITE),
+            wall: Tile::new("██████", BRIGHT_WHITE),
+            text: BRIGHT_WHITE,
+            background: Some(BLACK),
+        }
+    }
+
+    fn emoji() -> Self {
+        Theme {
+            wide: true,
+            player: Tile::new("🙂", WHITE),
+            robot1: Tile::new("🤖", WHITE),
+            robot2: Tile::new("👾", WHITE),
+            heap: Tile::new("💥", WHITE),
+            empty: Tile::new("  ", WHITE),
+            ..Theme::classic()
+        }
+    }
+
+    /*
+     * The colorblind palettes keep apart the player, the robots and the heaps
+     * with the colors (from the Okabe-Ito palette) still seen as different
+     * and with the lightness, the player is also bold
+     */
+
+    // Green-blind: blue against yellow and orange,
+    // an orange which is a dark red with 16 colors, far from the light yellow
+    fn deuteranopia() -> Self {
+        Theme {
+            player: Tile::new("@", Color::new(AnsiColor::LightYellow, 227, (240, 228, 66)))
+                .styled(&[Style::Bold]),
+            robot1: Tile::new("+", BRIGHT_WHITE),
+            robot2: Tile::new("#", Color::new(AnsiColor::LightBlue, 75, (86, 180, 233))),
+            heap: Tile::new("*", Color::new(AnsiColor::Red, 166, (213, 94, 0))),
+            ..Theme::classic()
+        }
+    }
+
+    // Red-blind: the reds look dark, so none of them
+    fn protanopia() -> Self {
+        Theme {
+            player: Tile::new("@", Color::new(AnsiColor::LightCyan, 117, (86, 180, 233)))
+                .styled(&[Style::Bold]),
+            robot1: Tile::new("+", BRIGHT_WHITE),
+            robot2: Tile::new("#", Color::new(AnsiColor::Yellow, 178, (230, 159, 0))),
+            heap: Tile::new("*", Color::new(AnsiColor::Blue, 25, (0, 114, 178))),
+            ..Theme::classic()
+        }
+    }
+
+    // Blue-blind: red against cyan
+    fn tritanopia() -> Self {
+        Theme {
+            player: Tile::new("@", Color::new(AnsiColor::LightRed, 203, (255, 95, 95)))
+                .styled(&[Style::Bold]),
+            robot1: Tile::new("+", BRIGHT_WHITE),
+            robot2: Tile::new("#", Color::new(AnsiColor::LightCyan, 44, (0, 215, 215))),
+            heap: Tile::new("*", Color::new(AnsiColor::Magenta, 175, (204, 121, 167))),
+            wall: Tile::new(
+                "╔╗╚╝═║",
+                Color::new(AnsiColor::LightBlack, 244, (128, 128, 128)),
+            ),
+            ..Theme::classic()
+        }
+    }
+
+    // No colors at all: the player in reverse video, the robots of type 2 bold
+    // and the heaps underlined
+    fn monochrome() -> Self {
+        let plain = Color::ansi(AnsiColor::Default);
+        Theme {
+            player: Tile::new("@", plain).styled(&[Style::Reverse, Style::Bold]),
+            robot1: Tile::new("+", plain),
+            robot2: Tile::new("#", plain).styled(&[Style::Bold]),
+            heap: Tile::new("*", plain).styled(&[Style::Underline]),
+            empty: Tile::new(" ", plain),
+            wall: Tile::new("╔╗╚╝═║", plain),
+            ..Theme::bsd()
+        }
+    }
+
+    pub fn get_theme(theme: Themes) -> Self {
+        match theme {
+            Themes::Classic => Theme::classic(),
+            Themes::Bsd => Theme::bsd(),
+            Themes::HighContrast => Theme::high_contrast(),
+            Themes::Emoji => Theme::emoji(),
+            Themes::Deuteranopia => Theme::deuteranopia(),
+            Themes::Protanopia => Theme::protanopia(),
+            Themes::Tritanopia => Theme::tritanopia(),
+            Themes::Monochrome => Theme::monochrome(),
+        }
+    }
+
+    // When a tile has a style, the inks start resetting the one before
+    pub fn is_styled(&self) -> bool {
+        [
+            &self.player,
+            &self.robot1,
+            &self.robot2,
+            &self.heap,
+            &self.empty,
+            &self.wall,
+        ]
+        .iter()
+        .any(|tile| !tile.style.is_empty())
+    }
+
+    // The same theme with only ascii characters: the others are taken from the classic theme
+    pub fn to_ascii(&self) -> Self {
+        let classic = Theme::classic();
+        let ascii = |tile: &Tile, classic: Tile| {
+            if tile.glyph.is_ascii() && !self.wide {
+                tile.clone()
+            } else {
+                Tile {
+                    glyph: classic.glyph,
+                    ..tile.clone()
+                }
+            }
+        };
+
+        Theme {
+            wide: false,
+            player: ascii(&self.player, classic.player),
+            robot1: ascii(&self.robot1, classic.robot1),
+            robot2: ascii(&self.robot2, classic.robot2),
+            heap: ascii(&self.heap, classic.heap),
+            empty: ascii(&self.empty, classic.empty),
+            wall: ascii(&self.wall, Tile::new("++++-|", classic.wall.color)),
+            ..self.clone()
+        }
+    }
+
+    // The problems of this theme, one per line
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        let tiles = [
+            ("player", &self.player),
+            ("robot1", &self.robot1),
+            ("robot2", &self.robot2),
+            ("heap", &self.heap),
+            ("empty", &self.empty),
+        ];
+        for (name, tile) in tiles {
+            let glyphs = tile.glyph.chars().count();
+            if self.wide && glyphs != 1 && glyphs != 2 {
+                errors.push(format!(
+                    "{}: '{}' is not one wide or two normal characters",
+                    name, tile.glyph
+                ));
+            } else if !self.wide && glyphs != 1 {
+                errors.push(format!(
+                    "{}: '{}' is not a single character",
+                    name, tile.glyph
+                ));
+            }
+        }
+
+        if self.wall.glyph.chars().count() != 6 {
+            errors.push(format!(
+                "wall: '{}' is not 6 characters (corners, horizontal and vertical)",
+                self.wall.glyph
+            ));
+        }
+
+        errors
+    }
+}
+
+/*
+ * User themes are JSON files with the fields of Theme in the themes
+ * config directory (~/.config/daleks/themes), the file name is the theme name
+ */
+pub struct UserTheme {
+    pub name: String,
+    pub theme: Result<Theme, String>,
+}
+
+pub fn user_themes() -> Vec<UserTheme> {
+    let xdg_dirs = xdg::BaseDirectories::with_prefix("daleks").unwrap();
+
+    load_user_themes(xdg_dirs.list_config_files_once("themes"))
+}
+
+fn load_user_themes(paths: Vec<PathBuf>) -> Vec<UserTheme> {
+    let mut themes: Vec<UserTheme> = paths
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_str()?.to_string();
+            let theme = load_user_theme(&path);
+            Some(UserTheme { name, theme })
+        })
+        .collect();
+
+    themes.sort_by(|a, b| a.name.cmp(&b.name));
+    themes
+}
+
+fn load_user_theme(path: &Path) -> Result<Theme, String> {
+    let serialized = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    let theme: Theme =
+        serde_json::from_str(&serialized).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    let errors = theme.validate();
+    if !errors.is_empty() {
+        return Err(format!("{}: {}", path.display(), errors.join(", ")));
+    }
+
+    Ok(theme)
+}
+
+// A built-in theme or else a user theme, by name (ignoring case)
+pub fn find(name: &str) -> Result<Theme, String> {
+    if let Ok(theme) = name.parse::<Themes>() {
+        return Ok(Theme::get_theme(theme));
+    }
+
+    match user_themes()
+        .into_iter()
+        .find(|user_theme| user_theme.name.eq_ignore_ascii_case(name))
+    {
+        Some(user_theme) => user_theme.theme,
+        None => Err(format!(
+            "'{}' is not a built-in or user theme (see --list-themes)",
+            name
+        )),
+    }
+}
+
+pub fn format_list() -> String {
+    let mut text = format!(
+        "{:<28}{:<8}{:<8}{:<8}{}\n",
+        "THEME", "PLAYER", "ROBOT1", "ROBOT2", "HEAP"
+    );
+
+    let mut errors = String::new();
+
+    let builtin = Themes::ALL
+        .iter()
+        .map(|&theme| (theme.to_string(), Ok(Theme::get_theme(theme))));
+    let user = user_themes()
+        .into_iter()
+        .map(|user_theme| (format!("{} (user)", user_theme.name), user_theme.theme));
+
+    for (name, theme) in builtin.chain(user) {
+        match theme {
+            // the glyphs are not padded by format, a wide one is two columns
+            Ok(theme) => {
+                let mut line = format!("{:<28}", name);
+                for tile in [&theme.player, &theme.robot1, &theme.robot2, &theme.heap] {
+                    let width = tile
+                        .glyph
+                        .chars()
+                        .count()
+                        .max(if theme.wide { 2 } else { 1 });
+                    line.push_str(&tile.glyph);
+                    line.push_str(&" ".repeat(8 - width));
+                }
+                text.push_str(line.trim_end());
+                text.push('\n');
+            }
+            Err(e) => errors.push_str(&format!("ERROR: {}\n", e)),
+        }
+    }
+
+    text + &errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_themes() {
+        let dir = std::env::temp_dir().join(format!("daleks-themes-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut ocean = Theme::get_theme(Themes::Classic);
+        ocean.background = Some(Color {
+            ansi: AnsiColor::Blue,
+            ansi256: None,
+            rgb: Some((0, 0, 95)),
+        });
+        ocean.player.style = vec![Style::Bold, Style::Underline];
+        fs::write(
+            dir.join("ocean.json"),
+            serde_json::to_string(&ocean).unwrap(),
+        )
+        .unwrap();
+        let mut broken = Theme::get_theme(Themes::Classic);
+        broken.player.glyph = "@@".to_string();
+        fs::write(
+            dir.join("broken.json"),
+            serde_json::to_string(&broken).unwrap(),
+        )
+        .unwrap();
+
+        let paths = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        let themes = load_user_themes(paths);
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(themes.len(), 2);
+        assert!(themes[0]
+            .theme
+            .as_ref()
+            .unwrap_err()
+            .contains("player: '@@' is not a single character"));
+        assert_eq!(themes[1].theme, Ok(ocean.clone()));
+
+        // the best color the terminal can show
+        let background = ocean.background.unwrap();
+        assert_eq!(background.bg(ColorDepth::TrueColor), "\x1b[48;2;0;0;95m");
+        assert_eq!(background.bg(ColorDepth::Ansi256), "\x1b[48;5;4m");
+        assert_eq!(background.bg(ColorDepth::Ansi16), "\x1b[48;5;4m");
+    }
+
+    // A light color and its dark one are too close to tell apart without the hue
+    fn hue(color: AnsiColor) -> u8 {
+        match color as u8 {
+            value if value >= AnsiColor::LightBlack as u8 => value - 8,
+            value => value,
+        }
+    }
+
+    #[test]
+    fn colorblind_themes() {
+        for name in [
+            Themes::Deuteranopia,
+            Themes::Protanopia,
+            Themes::Tritanopia,
+            Themes::Monochrome,
+        ] {
+            let theme = Theme::get_theme(name);
+            let tiles = [&theme.player, &theme.robot1, &theme.robot2, &theme.heap];
+            for (i, a) in tiles.iter().enumerate() {
+                for b in &tiles[i + 1..] {
+                    // only the same color is told apart by the style
+                    let distinct = if a.color == b.color {
+                        a.style != b.style
+                    } else {
+                        hue(a.color.ansi) != hue(b.color.ansi)
+                    };
+                    assert!(
+                        distinct,
+                        "{:?}: '{}' and '{}' look the same with 16 colors",
+                        name, a.glyph, b.glyph
+                    );
+                }
+            }
+        }
+    }
+}